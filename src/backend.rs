@@ -4,24 +4,164 @@ pub mod cpal;
 #[cfg(feature = "oboe")]
 pub mod oboe;
 
+pub mod manual;
+pub mod null;
+pub mod shared;
+
+#[cfg(feature = "cpal")]
+mod dither;
+#[cfg(feature = "cpal")]
+pub use dither::DitherType;
+
 use crate::{
-    mixer::{Mixer, MixerCommand},
+    mixer::{Mixer, MixerCommand, MixerState},
     LatencyRecorder,
 };
 use anyhow::Result;
 use ringbuf::HeapConsumer;
+#[cfg(all(
+    any(feature = "cpal", feature = "oboe"),
+    any(target_os = "linux", target_os = "android")
+))]
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::{any::Any, fmt, sync::Arc};
 
 pub struct BackendSetup {
     pub(crate) mixer_cons: HeapConsumer<MixerCommand>,
     pub(crate) latency_rec: LatencyRecorder,
+    pub(crate) mixer_state: Arc<MixerState>,
 }
 
-pub trait Backend {
+/// Description of a backend's output stream, for diagnostics. Fields are
+/// `None` until the stream is open or when the backend can't tell.
+#[derive(Debug, Clone, Default)]
+pub struct BackendInfo {
+    pub name: &'static str,
+    pub sample_rate: Option<u32>,
+    /// Frames per device buffer.
+    pub buffer_size: Option<u32>,
+    pub channels: Option<u16>,
+    /// Sample format written to the device, e.g. `F32`.
+    pub format: Option<String>,
+}
+
+impl fmt::Display for BackendInfo {
+    /// Formats as e.g. `CPAL, 48000 Hz, 256 frames, F32`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        if let Some(sample_rate) = self.sample_rate {
+            write!(f, ", {sample_rate} Hz")?;
+        }
+        if let Some(buffer_size) = self.buffer_size {
+            write!(f, ", {buffer_size} frames")?;
+        }
+        if let Some(channels) = self.channels {
+            write!(f, ", {channels} ch")?;
+        }
+        if let Some(format) = &self.format {
+            write!(f, ", {format}")?;
+        }
+        Ok(())
+    }
+}
+
+pub trait Backend: Any {
     fn setup(&mut self, setup: BackendSetup) -> Result<()>;
     fn start(&mut self) -> Result<()>;
+    /// Halts the output stream, keeping the mixer so [`Backend::start`] can resume it.
+    ///
+    /// Backends without a stream to halt can keep the default, which does nothing.
+    fn stop(&mut self) -> Result<()> {
+        Ok(())
+    }
     fn consume_broken(&self) -> bool;
+
+    /// Describes the output stream. The default reports nothing.
+    fn info(&self) -> BackendInfo {
+        BackendInfo::default()
+    }
+}
+
+/// Requests real-time scheduling for the audio callback thread on its first callback.
+#[cfg(any(feature = "cpal", feature = "oboe"))]
+struct RealtimePromoter {
+    pending: bool,
 }
 
+#[cfg(any(feature = "cpal", feature = "oboe"))]
+impl RealtimePromoter {
+    fn new(enabled: bool) -> Self {
+        Self { pending: enabled }
+    }
+
+    #[inline]
+    fn ensure(&mut self) {
+        if self.pending {
+            self.pending = false;
+            promote_current_thread();
+        }
+    }
+}
+
+/// OS error of the last failed [`promote_current_thread`], `0` if none is
+/// pending. The audio thread can't log, so the control thread reports it.
+#[cfg(all(
+    any(feature = "cpal", feature = "oboe"),
+    any(target_os = "linux", target_os = "android")
+))]
+static PROMOTE_ERROR: AtomicI32 = AtomicI32::new(0);
+
+/// Switches the calling thread to `SCHED_FIFO`. This usually needs `CAP_SYS_NICE`,
+/// an rtkit/limits.conf grant, or (on Android) is only honored for system apps.
+#[cfg(all(
+    any(feature = "cpal", feature = "oboe"),
+    any(target_os = "linux", target_os = "android")
+))]
+fn promote_current_thread() {
+    unsafe {
+        let priority = (libc::sched_get_priority_min(libc::SCHED_FIFO)
+            + libc::sched_get_priority_max(libc::SCHED_FIFO))
+            / 2;
+        let param = libc::sched_param {
+            sched_priority: priority,
+        };
+        let err = libc::pthread_setschedparam(libc::pthread_self(), libc::SCHED_FIFO, &param);
+        if err != 0 {
+            PROMOTE_ERROR.store(err, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Logs a real-time promotion failure recorded by the audio thread, once.
+#[cfg(all(
+    any(feature = "cpal", feature = "oboe"),
+    any(target_os = "linux", target_os = "android")
+))]
+pub(crate) fn report_promote_failure() {
+    let err = PROMOTE_ERROR.swap(0, Ordering::Relaxed);
+    if err != 0 {
+        crate::log::log(
+            crate::log::LogLevel::Warn,
+            &format!(
+                "failed to set real-time priority: {}",
+                std::io::Error::from_raw_os_error(err)
+            ),
+        );
+    }
+}
+
+#[cfg(all(
+    any(feature = "cpal", feature = "oboe"),
+    not(any(target_os = "linux", target_os = "android"))
+))]
+fn promote_current_thread() {}
+
+#[cfg(not(all(
+    any(feature = "cpal", feature = "oboe"),
+    any(target_os = "linux", target_os = "android")
+)))]
+pub(crate) fn report_promote_failure() {}
+
 #[repr(transparent)]
 struct StateCell {
     _data: (Mixer, LatencyRecorder),
@@ -39,7 +179,7 @@ impl StateCell {
 impl From<BackendSetup> for StateCell {
     fn from(value: BackendSetup) -> Self {
         Self {
-            _data: (Mixer::new(0, value.mixer_cons), value.latency_rec),
+            _data: (Mixer::new(0, value.mixer_cons, value.mixer_state), value.latency_rec),
         }
     }
 }
@@ -1,24 +1,86 @@
-use crate::Backend;
+pub use cpal::HostId;
+
+use crate::{
+    log::{log, LogLevel},
+    mixer::Mixer,
+    Backend, LatencyRecorder,
+};
 use anyhow::{Context, Result};
 use cpal::{
     traits::{DeviceTrait, HostTrait, StreamTrait},
-    BufferSize, OutputCallbackInfo, Stream, StreamError,
+    BufferSize, BuildStreamError, Device, OutputCallbackInfo, SampleFormat, SizedSample, Stream,
+    StreamConfig, StreamError,
 };
 use std::sync::{
     atomic::{AtomicBool, Ordering},
     Arc,
 };
 
-use super::{BackendSetup, StateCell};
+use super::{dither::Dither, BackendInfo, BackendSetup, DitherType, RealtimePromoter, StateCell};
 
 #[derive(Debug, Clone, Default)]
 pub struct CpalSettings {
+    /// Host API to open the stream on, e.g. `HostId::Asio` on Windows.
+    /// `None` uses the platform default. ASIO needs cpal's `asio` feature
+    /// and installed drivers.
+    pub host_id: Option<HostId>,
     pub buffer_size: Option<u32>,
+    /// Dither applied when the device only accepts integer samples.
+    pub dither: DitherType,
+    /// Request `SCHED_FIFO` for the callback thread on Linux. Needs real-time
+    /// privileges (e.g. rtkit or `CAP_SYS_NICE`); ignored on other platforms.
+    pub realtime_priority: bool,
+}
+
+fn render(mixer: &mut Mixer, channels: usize, data: &mut [f32]) {
+    match channels {
+        1 => mixer.render_mono(data),
+        2 => mixer.render_stereo(data),
+        _ => mixer.render_multichannel(data, channels),
+    }
+}
+
+fn record_latency(rec: &mut LatencyRecorder, info: &OutputCallbackInfo) {
+    let ts = info.timestamp();
+    if let Some(delay) = ts.playback.duration_since(&ts.callback) {
+        rec.push(delay.as_secs_f64());
+    }
+}
+
+/// Mixes into an f32 scratch buffer and converts it to the device's integer format.
+fn build_int_stream<T: SizedSample + Send + 'static>(
+    device: &Device,
+    config: &StreamConfig,
+    state: Arc<StateCell>,
+    error_callback: impl FnMut(StreamError) + Send + 'static,
+    mut promoter: RealtimePromoter,
+    mut dither: Dither,
+    convert: fn(&mut Dither, f32) -> T,
+) -> Result<Stream, BuildStreamError> {
+    let channels = config.channels as usize;
+    let mut buffer = Vec::new();
+    device.build_output_stream(
+        config,
+        move |data: &mut [T], info: &OutputCallbackInfo| {
+            promoter.ensure();
+            let (mixer, rec) = state.get();
+            buffer.resize(data.len(), 0.);
+            render(mixer, channels, &mut buffer);
+            for (output, sample) in data.iter_mut().zip(&buffer) {
+                *output = convert(&mut dither, *sample);
+            }
+            record_latency(rec, info);
+        },
+        error_callback,
+        None,
+    )
 }
 
 pub struct CpalBackend {
     settings: CpalSettings,
     stream: Option<Stream>,
+    host_id: Option<HostId>,
+    info: BackendInfo,
     broken: Arc<AtomicBool>,
     state: Option<Arc<StateCell>>,
 }
@@ -28,10 +90,21 @@ impl CpalBackend {
         Self {
             settings,
             stream: None,
+            host_id: None,
+            info: BackendInfo {
+                name: "CPAL",
+                ..Default::default()
+            },
             broken: Arc::default(),
             state: None,
         }
     }
+
+    /// The host API the stream was opened on, if running. Reports the
+    /// resolved host even when [`CpalSettings::host_id`] is `None`.
+    pub fn active_host(&self) -> Option<HostId> {
+        self.host_id
+    }
 }
 
 impl Backend for CpalBackend {
@@ -41,18 +114,22 @@ impl Backend for CpalBackend {
     }
 
     fn start(&mut self) -> Result<()> {
-        let host = cpal::default_host();
-        let device = match host.default_output_device() { 
-            Some(device) => device, 
-            None => { 
-                eprintln!("no default output device is found"); 
-                return Ok(());
-            },
+        // Two live streams would render the same mixer concurrently.
+        self.stream = None;
+        self.host_id = None;
+        let host = match self.settings.host_id {
+            Some(id) => cpal::host_from_id(id)
+                .with_context(|| format!("audio host {} is unavailable", id.name()))?,
+            None => cpal::default_host(),
         };
-        let mut config = device
+        let device = host
+            .default_output_device()
+            .with_context(|| format!("no default output device on {}", host.id().name()))?;
+        let supported_config = device
             .default_output_config()
-            .context("cannot get output config")?
-            .config();
+            .context("cannot get output config")?;
+        let sample_format = supported_config.sample_format();
+        let mut config = supported_config.config();
         config.buffer_size = self
             .settings
             .buffer_size
@@ -60,49 +137,77 @@ impl Backend for CpalBackend {
 
         let broken = Arc::clone(&self.broken);
         let error_callback = move |err| {
-            eprintln!("audio error: {err:?}");
+            log(LogLevel::Error, &format!("audio error: {err:?}"));
             if matches!(err, StreamError::DeviceNotAvailable) {
                 broken.store(true, Ordering::Relaxed);
             }
         };
         let state = Arc::clone(self.state.as_ref().unwrap());
-        state.get().0.sample_rate = config.sample_rate.0;
-        let stream = (if config.channels == 1 {
-            device.build_output_stream(
+        state.get().0.set_sample_rate(config.sample_rate.0);
+        let mut promoter = RealtimePromoter::new(self.settings.realtime_priority);
+        let dither = Dither::new(self.settings.dither);
+        let stream = (match sample_format {
+            SampleFormat::I16 => build_int_stream(
+                &device,
                 &config,
-                move |data: &mut [f32], info: &OutputCallbackInfo| {
-                    let (mixer, rec) = state.get();
-                    mixer.render_mono(data);
-                    let ts = info.timestamp();
-                    if let Some(delay) = ts.playback.duration_since(&ts.callback) {
-                        rec.push(delay.as_secs_f64());
-                    }
-                },
+                state,
                 error_callback,
-                None,
-            )
-        } else {
-            device.build_output_stream(
+                promoter,
+                dither,
+                Dither::quantize_i16,
+            ),
+            SampleFormat::I32 => build_int_stream(
+                &device,
                 &config,
-                move |data: &mut [f32], info: &OutputCallbackInfo| {
-                    let (mixer, rec) = state.get();
-                    mixer.render_stereo(data);
-                    let ts = info.timestamp();
-                    if let Some(delay) = ts.playback.duration_since(&ts.callback) {
-                        rec.push(delay.as_secs_f64());
-                    }
-                },
+                state,
                 error_callback,
-                None
-            )
+                promoter,
+                dither,
+                Dither::quantize_i32,
+            ),
+            _ => {
+                let channels = config.channels as usize;
+                device.build_output_stream(
+                    &config,
+                    move |data: &mut [f32], info: &OutputCallbackInfo| {
+                        promoter.ensure();
+                        let (mixer, rec) = state.get();
+                        render(mixer, channels, data);
+                        record_latency(rec, info);
+                    },
+                    error_callback,
+                    None,
+                )
+            }
         })
         .context("failed to build stream")?;
         stream.play()?;
         self.stream = Some(stream);
+        self.host_id = Some(host.id());
+        self.info = BackendInfo {
+            name: "CPAL",
+            sample_rate: Some(config.sample_rate.0),
+            buffer_size: match config.buffer_size {
+                BufferSize::Fixed(frames) => Some(frames),
+                BufferSize::Default => None,
+            },
+            channels: Some(config.channels),
+            format: Some(format!("{sample_format:?}")),
+        };
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        self.stream = None;
+        self.host_id = None;
         Ok(())
     }
 
     fn consume_broken(&self) -> bool {
         self.broken.fetch_and(false, Ordering::Relaxed)
     }
+
+    fn info(&self) -> BackendInfo {
+        self.info.clone()
+    }
 }
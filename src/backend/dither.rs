@@ -0,0 +1,55 @@
+/// Noise added when downconverting f32 output to integer formats.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DitherType {
+    None,
+    Rectangular,
+    #[default]
+    Triangular,
+}
+
+/// Quantizes f32 samples to integer formats with the configured dither.
+pub(crate) struct Dither {
+    kind: DitherType,
+    seed: u32,
+}
+
+impl Dither {
+    pub(crate) fn new(kind: DitherType) -> Self {
+        Self {
+            kind,
+            seed: 0x9E37_79B9,
+        }
+    }
+
+    #[inline(always)]
+    fn uniform(&mut self) -> f32 {
+        // xorshift32
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 17;
+        self.seed ^= self.seed << 5;
+        (self.seed >> 8) as f32 / (1 << 24) as f32
+    }
+
+    /// Quantizes `sample` to a signed integer with `bits` significant bits.
+    #[inline]
+    pub(crate) fn quantize(&mut self, sample: f32, bits: u32) -> i32 {
+        let scale = (1u32 << (bits - 1)) as f32;
+        let noise = match self.kind {
+            DitherType::None => 0.,
+            DitherType::Rectangular => self.uniform() - 0.5,
+            DitherType::Triangular => self.uniform() - self.uniform(),
+        };
+        (sample * scale + noise).round().clamp(-scale, scale - 1.) as i32
+    }
+
+    #[inline]
+    pub(crate) fn quantize_i16(&mut self, sample: f32) -> i16 {
+        self.quantize(sample, 16) as i16
+    }
+
+    /// 24-bit devices are exposed as `i32`; dither at the 24-bit LSB and left-align.
+    #[inline]
+    pub(crate) fn quantize_i32(&mut self, sample: f32) -> i32 {
+        self.quantize(sample, 24) << 8
+    }
+}
@@ -0,0 +1,129 @@
+use super::{BackendInfo, BackendSetup};
+use crate::{mixer::Mixer, AudioManager, Backend, Frame};
+use anyhow::Result;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
+
+/// A backend without an audio device or thread: frames are only produced
+/// when [`ManualBackend::render`] is called, which makes time-dependent
+/// behaviour deterministic in tests.
+///
+/// Clones share the same mixer, so keep one to drive the `AudioManager`
+/// that owns the other. While stopped, including after that manager is
+/// dropped, rendering yields silence without running the mixer.
+#[derive(Clone)]
+pub struct ManualBackend {
+    sample_rate: u32,
+    channels: usize,
+    mixer: Arc<Mutex<Option<Mixer>>>,
+    running: Arc<AtomicBool>,
+}
+
+impl ManualBackend {
+    /// Creates a backend rendering `channels` interleaved channels. Layouts
+    /// wider than stereo go through the mixer's multichannel routing.
+    pub fn new(sample_rate: u32, channels: usize) -> Self {
+        assert!(channels > 0, "channel count must be positive");
+        Self {
+            sample_rate,
+            channels,
+            mixer: Arc::default(),
+            running: Arc::default(),
+        }
+    }
+
+    #[inline(always)]
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    #[inline(always)]
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// Synchronously pulls `frames` frames through the mixer. Mono output is
+    /// duplicated to both channels; wider layouts keep the first two.
+    pub fn render(&self, frames: usize) -> Vec<Frame> {
+        let data = self.render_interleaved(frames);
+        data.chunks_exact(self.channels)
+            .map(|frame| Frame(frame[0], frame[self.channels.min(2) - 1]))
+            .collect()
+    }
+
+    /// Like [`ManualBackend::render`], but returns every channel as
+    /// interleaved samples.
+    pub fn render_interleaved(&self, frames: usize) -> Vec<f32> {
+        let mut data = vec![0.; frames * self.channels];
+        let mut mixer = self.mixer.lock().unwrap();
+        if !self.running.load(Ordering::Relaxed) {
+            return data;
+        }
+        if let Some(mixer) = mixer.as_mut() {
+            match self.channels {
+                1 => mixer.render_mono(&mut data),
+                2 => mixer.render_stereo(&mut data),
+                channels => mixer.render_multichannel(&mut data, channels),
+            }
+        }
+        data
+    }
+}
+
+impl Backend for ManualBackend {
+    fn setup(&mut self, setup: BackendSetup) -> Result<()> {
+        *self.mixer.lock().unwrap() = Some(Mixer::new(
+            self.sample_rate,
+            setup.mixer_cons,
+            setup.mixer_state,
+        ));
+        Ok(())
+    }
+
+    fn start(&mut self) -> Result<()> {
+        self.running.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Takes the mixer lock, so no render is in progress once this returns.
+    fn stop(&mut self) -> Result<()> {
+        let _mixer = self.mixer.lock().unwrap();
+        self.running.store(false, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn consume_broken(&self) -> bool {
+        false
+    }
+
+    fn info(&self) -> BackendInfo {
+        BackendInfo {
+            name: "Manual",
+            sample_rate: Some(self.sample_rate),
+            buffer_size: None,
+            channels: Some(self.channels as u16),
+            format: Some("F32".to_owned()),
+        }
+    }
+}
+
+/// Renders `duration` seconds of audio without a device, as interleaved
+/// samples with `channels` channels.
+///
+/// `setup` adds renderers to a fresh manager and starts them. Whatever it
+/// returns, typically the handles, is kept alive until rendering is done.
+pub fn render_offline<T>(
+    duration: f64,
+    sample_rate: u32,
+    channels: usize,
+    setup: impl FnOnce(&mut AudioManager) -> Result<T>,
+) -> Result<Vec<f32>> {
+    let backend = ManualBackend::new(sample_rate, channels);
+    let mut manager = AudioManager::new(backend.clone())?;
+    let handles = setup(&mut manager)?;
+    let data = backend.render_interleaved((duration * sample_rate as f64).round() as usize);
+    drop(handles);
+    Ok(data)
+}
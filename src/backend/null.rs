@@ -0,0 +1,113 @@
+use super::{BackendInfo, BackendSetup};
+use crate::{mixer::Mixer, Backend};
+use anyhow::{Context, Result};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+/// Length of the blocks the render thread produces.
+const BLOCK_TIME: Duration = Duration::from_millis(10);
+
+/// A backend without an audio device: a thread renders the mix in real time
+/// and discards it. Commands are consumed and renderers advance as on a real
+/// device, so apps keep running silently when no output is available.
+pub struct NullBackend {
+    sample_rate: u32,
+    channels: usize,
+    mixer: Arc<Mutex<Option<Mixer>>>,
+    running: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl NullBackend {
+    pub fn new(sample_rate: u32, channels: usize) -> Self {
+        assert!(channels > 0, "channel count must be positive");
+        Self {
+            sample_rate,
+            channels,
+            mixer: Arc::default(),
+            running: Arc::default(),
+            thread: None,
+        }
+    }
+}
+
+impl Backend for NullBackend {
+    fn setup(&mut self, setup: BackendSetup) -> Result<()> {
+        *self.mixer.lock().unwrap() = Some(Mixer::new(
+            self.sample_rate,
+            setup.mixer_cons,
+            setup.mixer_state,
+        ));
+        Ok(())
+    }
+
+    fn start(&mut self) -> Result<()> {
+        if self.thread.is_some() {
+            return Ok(());
+        }
+        self.running.store(true, Ordering::Relaxed);
+        let mixer = Arc::clone(&self.mixer);
+        let running = Arc::clone(&self.running);
+        let channels = self.channels;
+        let frames = (self.sample_rate as f64 * BLOCK_TIME.as_secs_f64()).round() as usize;
+        let period = Duration::from_secs_f64(frames as f64 / self.sample_rate as f64);
+        let thread = thread::Builder::new()
+            .name("sasa-null".to_owned())
+            .spawn(move || {
+                let mut data = vec![0.; frames * channels];
+                let mut deadline = Instant::now();
+                while running.load(Ordering::Relaxed) {
+                    if let Some(mixer) = mixer.lock().unwrap().as_mut() {
+                        match channels {
+                            1 => mixer.render_mono(&mut data),
+                            2 => mixer.render_stereo(&mut data),
+                            channels => mixer.render_multichannel(&mut data, channels),
+                        }
+                    }
+                    deadline += period;
+                    if let Some(wait) = deadline.checked_duration_since(Instant::now()) {
+                        thread::sleep(wait);
+                    }
+                }
+            })
+            .context("spawn null backend thread")?;
+        self.thread = Some(thread);
+        Ok(())
+    }
+
+    /// Returns once the render thread has exited, so no block is rendered
+    /// afterwards.
+    fn stop(&mut self) -> Result<()> {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        Ok(())
+    }
+
+    fn consume_broken(&self) -> bool {
+        false
+    }
+
+    fn info(&self) -> BackendInfo {
+        BackendInfo {
+            name: "Null",
+            sample_rate: Some(self.sample_rate),
+            buffer_size: Some((self.sample_rate as f64 * BLOCK_TIME.as_secs_f64()).round() as u32),
+            channels: Some(self.channels as u16),
+            format: Some("F32".to_owned()),
+        }
+    }
+}
+
+impl Drop for NullBackend {
+    fn drop(&mut self) {
+        let _ = self.stop();
+    }
+}
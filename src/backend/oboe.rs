@@ -1,10 +1,13 @@
-pub use oboe::{PerformanceMode, SharingMode, Usage};
+pub use oboe::{AudioApi, PerformanceMode, SharingMode, Usage};
 
-use super::{BackendSetup, StateCell};
-use crate::Backend;
+use super::{BackendInfo, BackendSetup, RealtimePromoter, StateCell};
+use crate::{
+    log::{log, LogLevel},
+    Backend,
+};
 use anyhow::Result;
 use oboe::{
-    AudioFormat, AudioOutputCallback, AudioOutputStreamSafe, AudioStream, AudioStreamAsync, AudioStreamBuilder, DataCallbackResult, Output, Stereo, Unspecified
+    AudioFormat, AudioOutputCallback, AudioOutputStreamSafe, AudioStream, AudioStreamAsync, AudioStreamBase, AudioStreamBuilder, DataCallbackResult, Output, Stereo, Unspecified
 };
 use std::sync::{
     atomic::{AtomicBool, Ordering},
@@ -16,6 +19,12 @@ pub struct OboeSettings {
     pub performance_mode: PerformanceMode,
     pub sharing_mode: SharingMode,
     pub usage: Usage,
+    /// Preferred native API; `Unspecified` lets oboe choose.
+    pub audio_api: AudioApi,
+    /// Request `SCHED_FIFO` for the callback thread. AAudio already raises the
+    /// priority of low-latency streams where allowed; this only helps on
+    /// devices that grant the app real-time scheduling.
+    pub realtime_priority: bool,
 }
 impl Default for OboeSettings {
     fn default() -> Self {
@@ -24,6 +33,8 @@ impl Default for OboeSettings {
             performance_mode: PerformanceMode::None,
             sharing_mode: SharingMode::Shared,
             usage: Usage::Media,
+            audio_api: AudioApi::Unspecified,
+            realtime_priority: false,
         }
     }
 }
@@ -46,6 +57,34 @@ impl OboeBackend {
     }
 }
 
+impl OboeBackend {
+    /// Changes the performance mode, reopening a running stream. Renderers are
+    /// kept since the mixer outlives the stream.
+    pub fn set_performance_mode(&mut self, mode: PerformanceMode) -> Result<()> {
+        self.settings.performance_mode = mode;
+        self.restart_if_running()
+    }
+
+    /// Changes the requested buffer size in frames, reopening a running stream.
+    pub fn set_buffer_size(&mut self, frames: Option<u32>) -> Result<()> {
+        self.settings.buffer_size = frames;
+        self.restart_if_running()
+    }
+
+    /// The native API oboe actually opened the stream with, if running.
+    pub fn active_api(&self) -> Option<AudioApi> {
+        self.stream.as_ref().map(|stream| stream.get_audio_api())
+    }
+
+    fn restart_if_running(&mut self) -> Result<()> {
+        if self.stream.is_some() {
+            self.start()
+        } else {
+            Ok(())
+        }
+    }
+}
+
 impl Backend for OboeBackend {
     fn setup(&mut self, setup: BackendSetup) -> Result<()> {
         self.state = Some(Arc::new(setup.into()));
@@ -53,40 +92,71 @@ impl Backend for OboeBackend {
     }
 
     fn start(&mut self) -> Result<()> {
+        // Two live streams would render the same mixer concurrently.
+        self.stop()?;
         let mut stream = AudioStreamBuilder::default()
             .set_usage(self.settings.usage)
             .set_performance_mode(self.settings.performance_mode)
             .set_sharing_mode(self.settings.sharing_mode)
+            .set_audio_api(self.settings.audio_api)
             .set_channel_count::<Stereo>()
             .set_callback(OboeCallback::new(
                 Arc::clone(self.state.as_ref().unwrap()),
                 Arc::clone(&self.broken),
                 self.settings.buffer_size,
+                RealtimePromoter::new(self.settings.realtime_priority),
             ))
-            .open_stream()
-            .unwrap();
+            .open_stream()?;
         stream.start()?;
         self.stream = Some(stream);
         Ok(())
     }
 
+    fn stop(&mut self) -> Result<()> {
+        if let Some(mut stream) = self.stream.take() {
+            stream.stop()?;
+        }
+        Ok(())
+    }
+
     fn consume_broken(&self) -> bool {
         self.broken.fetch_and(false, Ordering::Relaxed)
     }
+
+    fn info(&self) -> BackendInfo {
+        let mut info = BackendInfo {
+            name: "Oboe",
+            channels: Some(2),
+            format: Some("F32".to_owned()),
+            ..Default::default()
+        };
+        if let Some(stream) = &self.stream {
+            info.sample_rate = Some(stream.get_sample_rate() as u32);
+            info.buffer_size = Some(stream.get_buffer_size_in_frames() as u32);
+        }
+        info
+    }
 }
 
 struct OboeCallback {
     state: Arc<StateCell>,
     broken: Arc<AtomicBool>,
     buffer_size: Option<u32>,
+    promoter: RealtimePromoter,
 }
 
 impl OboeCallback {
-    pub fn new(state: Arc<StateCell>, broken: Arc<AtomicBool>, buffer_size: Option<u32>) -> Self {
+    pub fn new(
+        state: Arc<StateCell>,
+        broken: Arc<AtomicBool>,
+        buffer_size: Option<u32>,
+        promoter: RealtimePromoter,
+    ) -> Self {
         Self {
             state,
             broken,
             buffer_size,
+            promoter,
         }
     }
 }
@@ -99,6 +169,7 @@ impl AudioOutputCallback for OboeCallback {
         stream: &mut dyn AudioOutputStreamSafe,
         frames: &mut [(oboe::Unspecified, oboe::Unspecified)],
     ) -> DataCallbackResult {
+        self.promoter.ensure();
         if let Some(buffer_size) = &self.buffer_size {
             let _ = stream.set_buffer_size_in_frames(
                 (*buffer_size as i32).min(stream.get_buffer_size_in_frames()),
@@ -109,7 +180,7 @@ impl AudioOutputCallback for OboeCallback {
         if let Ok(latency) = stream.calculate_latency_millis() {
             rec.push(latency / 1000.);
         }
-        mixer.sample_rate = stream.get_sample_rate() as u32;
+        mixer.set_sample_rate(stream.get_sample_rate() as u32);
         let raw = frames.as_mut_ptr();
         mixer.render_stereo(unsafe {
             std::slice::from_raw_parts_mut(raw as *mut f32, frames.len() * 2)
@@ -123,7 +194,7 @@ impl AudioOutputCallback for OboeCallback {
         _audio_stream: &mut dyn oboe::AudioOutputStreamSafe,
         error: oboe::Error,
     ) {
-        eprintln!("audio error: {error:?}");
+        log(LogLevel::Error, &format!("audio error: {error:?}"));
         self.broken.store(true, Ordering::Relaxed);
     }
 
@@ -132,7 +203,7 @@ impl AudioOutputCallback for OboeCallback {
         _audio_stream: &mut dyn oboe::AudioOutputStreamSafe,
         error: oboe::Error,
     ) {
-        eprintln!("audio error: {error:?}");
+        log(LogLevel::Error, &format!("audio error: {error:?}"));
         self.broken.store(true, Ordering::Relaxed);
     }
 }
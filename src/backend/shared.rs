@@ -0,0 +1,102 @@
+use super::{BackendInfo, BackendSetup};
+use crate::{mixer::Mixer, AudioManager, Backend, Renderer};
+use anyhow::Result;
+use std::sync::{Arc, Mutex};
+
+/// A backend that mixes into another [`AudioManager`]'s output instead of
+/// opening a device, so several managers can share one stream.
+///
+/// The host manager keeps the device; each manager built on a
+/// `SharedBackend` keeps its own renderers and controls, and is summed into
+/// the host as a single renderer until it is dropped.
+pub struct SharedBackend {
+    sample_rate: u32,
+    mixer: Arc<Mutex<Option<Mixer>>>,
+}
+
+impl SharedBackend {
+    pub fn new(host: &mut AudioManager) -> Result<Self> {
+        let mixer = Arc::default();
+        host.add_renderer(SharedRenderer {
+            mixer: Arc::clone(&mixer),
+            scratch: Vec::new(),
+        })?;
+        Ok(Self {
+            sample_rate: host.sample_rate().unwrap_or(48000),
+            mixer,
+        })
+    }
+}
+
+impl Backend for SharedBackend {
+    fn setup(&mut self, setup: BackendSetup) -> Result<()> {
+        *self.mixer.lock().unwrap() = Some(Mixer::new(
+            self.sample_rate,
+            setup.mixer_cons,
+            setup.mixer_state,
+        ));
+        Ok(())
+    }
+
+    fn start(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn consume_broken(&self) -> bool {
+        false
+    }
+
+    fn info(&self) -> BackendInfo {
+        BackendInfo {
+            name: "Shared",
+            sample_rate: Some(self.sample_rate),
+            ..Default::default()
+        }
+    }
+}
+
+struct SharedRenderer {
+    mixer: Arc<Mutex<Option<Mixer>>>,
+    /// The guest mixer overwrites its buffer, so it renders here first.
+    scratch: Vec<f32>,
+}
+
+impl SharedRenderer {
+    fn render(&mut self, sample_rate: u32, data: &mut [f32], channels: usize) {
+        // Only contended while the guest manager is being set up.
+        let Ok(mut mixer) = self.mixer.try_lock() else {
+            return;
+        };
+        let Some(mixer) = mixer.as_mut() else {
+            return;
+        };
+        mixer.set_sample_rate(sample_rate);
+        self.scratch.resize(data.len(), 0.);
+        if channels == 1 {
+            mixer.render_mono(&mut self.scratch);
+        } else {
+            mixer.render_stereo(&mut self.scratch);
+        }
+        for (output, sample) in data.iter_mut().zip(&self.scratch) {
+            *output += sample;
+        }
+    }
+}
+
+impl Renderer for SharedRenderer {
+    fn alive(&self) -> bool {
+        Arc::strong_count(&self.mixer) > 1
+    }
+
+    fn render_mono(&mut self, sample_rate: u32, data: &mut [f32]) {
+        self.render(sample_rate, data, 1);
+    }
+
+    fn render_stereo(&mut self, sample_rate: u32, data: &mut [f32]) {
+        self.render(sample_rate, data, 2);
+    }
+}
@@ -0,0 +1,12 @@
+/// Identifies an effect bus created with [`AudioManager::create_bus`](crate::AudioManager::create_bus).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BusId(pub(crate) u64);
+
+/// Processing shared by every renderer sending to a bus, e.g. a reverb.
+pub trait BusEffect: Send + Sync {
+    /// Processes the summed sends in place, interleaved with `channels`
+    /// channels. The result is added to the master mix.
+    ///
+    /// Called every block, even when nothing is sent, so tails can ring out.
+    fn process(&mut self, sample_rate: u32, channels: usize, data: &mut [f32]);
+}
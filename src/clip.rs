@@ -1,52 +1,260 @@
-use crate::Frame;
+use crate::{
+    tag::{ClipTag, Tagged},
+    Frame,
+};
 use anyhow::{anyhow, bail, Result};
-use std::{io::Cursor, sync::Arc};
+use atomic_float::AtomicF32;
+use std::{
+    hash::{DefaultHasher, Hash, Hasher},
+    io::Cursor,
+    path::Path,
+    ptr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
 use symphonia::core::{
-    audio::{AudioBufferRef, Signal},
+    audio::{AudioBufferRef, Channels, Signal},
     io::MediaSourceStream,
 };
 
+/// Interpolation kernel used when reading a clip between frames.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ResampleQuality {
+    #[default]
+    Linear,
+    /// Catmull-Rom cubic over four frames.
+    Cubic,
+    /// Hann-windowed sinc over eight frames.
+    Sinc,
+    /// The closest frame, without interpolation. Keeps the aliasing of
+    /// chiptune-style sounds when pitched.
+    NearestNeighbor,
+}
+
+const SINC_HALF_WIDTH: isize = 4;
+
+/// Default of [`AudioClip::set_max_decode_frames`]: 10 minutes at 48 kHz,
+/// about 220 MiB of frames.
+pub const DEFAULT_MAX_DECODE_FRAMES: u64 = 48000 * 60 * 10;
+
+static MAX_DECODE_FRAMES: AtomicU64 = AtomicU64::new(DEFAULT_MAX_DECODE_FRAMES);
+
+/// How sources with more than two channels are folded to stereo.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DownmixMode {
+    /// ITU-R BS.775 style: centre and surrounds at -3 dB, LFE dropped.
+    /// The result is not normalized and may exceed full scale.
+    #[default]
+    Itu,
+    /// Keep only the first two channels.
+    FrontPair,
+}
+
+/// Container and codec for [`AudioClip::warm_decoder`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WarmFormat {
+    Wav,
+    Mp3,
+    /// AAC in an ADTS stream.
+    Aac,
+}
+
+impl WarmFormat {
+    /// A few frames of silence in this format.
+    fn silence(self) -> Vec<u8> {
+        match self {
+            Self::Wav => AudioClip::from_raw(vec![Frame::default(); 16], 48000).to_wav_bytes(),
+            // MPEG-1 Layer III, 128 kbps, 44.1 kHz, mono: a 4-byte header and
+            // zeroed side info and main data, which decode to silence.
+            Self::Mp3 => {
+                let mut frame = vec![0; 417];
+                frame[..4].copy_from_slice(&[0xFF, 0xFB, 0x90, 0xC0]);
+                frame.repeat(4)
+            }
+            // ADTS AAC-LC, 48 kHz, mono: a 7-byte header and a single channel
+            // element with no scale factor bands, then the end element.
+            Self::Aac => {
+                [0xFF, 0xF1, 0x4C, 0x40, 0x01, 0x7F, 0xFC, 0x00, 0x00, 0x00, 0x07].repeat(4)
+            }
+        }
+    }
+}
+
+/// Left/right gains for one source channel under [`DownmixMode::Itu`].
+fn itu_coefficients(channel: Channels) -> (f32, f32) {
+    use std::f32::consts::FRAC_1_SQRT_2 as H;
+    let left = Channels::REAR_LEFT
+        | Channels::FRONT_LEFT_CENTRE
+        | Channels::SIDE_LEFT
+        | Channels::TOP_FRONT_LEFT
+        | Channels::TOP_REAR_LEFT
+        | Channels::REAR_LEFT_CENTRE
+        | Channels::FRONT_LEFT_WIDE
+        | Channels::FRONT_LEFT_HIGH;
+    let right = Channels::REAR_RIGHT
+        | Channels::FRONT_RIGHT_CENTRE
+        | Channels::SIDE_RIGHT
+        | Channels::TOP_FRONT_RIGHT
+        | Channels::TOP_REAR_RIGHT
+        | Channels::REAR_RIGHT_CENTRE
+        | Channels::FRONT_RIGHT_WIDE
+        | Channels::FRONT_RIGHT_HIGH;
+    if channel == Channels::FRONT_LEFT {
+        (1., 0.)
+    } else if channel == Channels::FRONT_RIGHT {
+        (0., 1.)
+    } else if channel == Channels::LFE1 || channel == Channels::LFE2 {
+        (0., 0.)
+    } else if left.contains(channel) {
+        (H, 0.)
+    } else if right.contains(channel) {
+        (0., H)
+    } else {
+        (H, H)
+    }
+}
+
+#[inline]
+fn catmull_rom(p0: f32, p1: f32, p2: f32, p3: f32, t: f32) -> f32 {
+    let a = -0.5 * p0 + 1.5 * p1 - 1.5 * p2 + 0.5 * p3;
+    let b = p0 - 2.5 * p1 + 2. * p2 - 0.5 * p3;
+    let c = -0.5 * p0 + 0.5 * p2;
+    ((a * t + b) * t + c) * t + p1
+}
+
+#[inline]
+fn windowed_sinc(x: f32) -> f32 {
+    if x == 0. {
+        return 1.;
+    }
+    let px = std::f32::consts::PI * x;
+    let window = 0.5 * (1. + (px / SINC_HALF_WIDTH as f32).cos());
+    px.sin() / px * window
+}
+
+/// What happened while decoding a clip, to catch truncated or corrupt assets
+/// that would otherwise just play short.
+#[derive(Debug, Clone, Default)]
+pub struct DecodeReport {
+    pub decoded_frames: u64,
+    /// Frame count declared by the container, if it has one.
+    pub expected_frames: Option<u64>,
+    /// Recoverable problems, e.g. skipped packets or missing frames.
+    pub warnings: Vec<String>,
+}
+
 struct ClipInner {
     frames: Vec<Frame>,
     sample_rate: u32,
+    /// Shared with derived clips that must follow this one's gain.
+    gain: Arc<AtomicF32>,
+    /// Set for clips decoded by [`AudioClip::new`] and friends.
+    report: Option<DecodeReport>,
 }
-pub struct AudioClip(Arc<ClipInner>);
+// `repr(C)` keeps the tag at the start, where the C API reads it.
+#[repr(C)]
+pub struct AudioClip(ClipTag, Arc<ClipInner>);
 impl Clone for AudioClip {
     fn clone(&self) -> Self {
-        Self(Arc::clone(&self.0))
+        Self(ClipTag::new(), Arc::clone(&self.1))
+    }
+}
+impl Tagged for AudioClip {
+    const MAGIC: u32 = ClipTag::MAGIC;
+
+    unsafe fn tag_ptr(ptr: *const Self) -> *const u32 {
+        ptr::addr_of!((*ptr).0).cast()
     }
 }
 
 impl AudioClip {
     pub fn from_raw(frames: Vec<Frame>, sample_rate: u32) -> Self {
-        Self(Arc::new(ClipInner {
-            frames,
-            sample_rate,
-        }))
+        Self::with_report(frames, sample_rate, None)
+    }
+
+    fn with_report(frames: Vec<Frame>, sample_rate: u32, report: Option<DecodeReport>) -> Self {
+        Self(
+            ClipTag::new(),
+            Arc::new(ClipInner {
+                frames,
+                sample_rate,
+                gain: Arc::new(AtomicF32::new(1.)),
+                report,
+            }),
+        )
+    }
+
+    /// A clip of `frames` at this clip's sample rate whose gain stays tied
+    /// to this one's, e.g. for pre-pitched copies.
+    pub(crate) fn with_shared_gain(&self, frames: Vec<Frame>) -> Self {
+        Self(
+            ClipTag::new(),
+            Arc::new(ClipInner {
+                frames,
+                sample_rate: self.1.sample_rate,
+                gain: Arc::clone(&self.1.gain),
+                report: None,
+            }),
+        )
     }
 
+    #[inline]
     pub fn decode(data: Vec<u8>) -> Result<(Vec<Frame>, u32)> {
+        Self::decode_with_downmix(data, DownmixMode::default())
+    }
+
+    /// Decodes `data` to stereo frames at its own sample rate. Integer PCM up
+    /// to 32 bits and 64-bit float are scaled to `f32` in `[-1, 1]`.
+    pub fn decode_with_downmix(data: Vec<u8>, downmix: DownmixMode) -> Result<(Vec<Frame>, u32)> {
+        let (frames, sample_rate, _) = Self::decode_with_report(data, downmix, None)?;
+        Ok((frames, sample_rate))
+    }
+
+    /// Stops decoding once `max_seconds` of audio are in, if given.
+    fn decode_with_report(
+        data: Vec<u8>,
+        downmix: DownmixMode,
+        max_seconds: Option<f64>,
+    ) -> Result<(Vec<Frame>, u32, DecodeReport)> {
         fn load_frames_from_buffer(
             frames: &mut Vec<Frame>,
             buffer: &symphonia::core::audio::AudioBuffer<f32>,
+            downmix: DownmixMode,
         ) {
-            match buffer.spec().channels.count() {
+            let channels = buffer.spec().channels;
+            match channels.count() {
                 1 => {
                     let chan = buffer.chan(0);
                     frames.reserve(chan.len());
                     frames.extend(chan.iter().map(|&it| Frame(it, it)));
                 }
-                _ => {
+                count if count == 2 || downmix == DownmixMode::FrontPair => {
                     let iter = buffer.chan(0).iter().zip(buffer.chan(1));
                     frames.reserve(iter.len());
                     frames.extend(iter.map(|(left, right)| Frame(*left, *right)))
                 }
+                _ => {
+                    let coefficients: Vec<_> = channels.iter().map(itu_coefficients).collect();
+                    frames.reserve(buffer.frames());
+                    frames.extend((0..buffer.frames()).map(|index| {
+                        coefficients.iter().enumerate().fold(
+                            Frame::default(),
+                            |acc, (chan, (left, right))| {
+                                let sample = buffer.chan(chan)[index];
+                                acc + Frame(sample * left, sample * right)
+                            },
+                        )
+                    }));
+                }
             }
         }
 
         fn load_frames_from_buffer_ref(
             frames: &mut Vec<Frame>,
             buffer: &AudioBufferRef,
+            downmix: DownmixMode,
         ) -> Result<()> {
             macro_rules! conv {
                 ($buffer:ident) => {{
@@ -55,12 +263,12 @@ impl AudioClip {
                         buffer.spec().clone(),
                     );
                     $buffer.convert(&mut dest);
-                    load_frames_from_buffer(frames, &dest);
+                    load_frames_from_buffer(frames, &dest, downmix);
                 }};
             }
             use AudioBufferRef::*;
             match buffer {
-                F32(buffer) => load_frames_from_buffer(frames, buffer),
+                F32(buffer) => load_frames_from_buffer(frames, buffer, downmix),
                 U8(buffer) => conv!(buffer),
                 U16(buffer) => conv!(buffer),
                 U24(buffer) => conv!(buffer),
@@ -91,9 +299,23 @@ impl AudioClip {
         let sample_rate = codec_params
             .sample_rate
             .ok_or_else(|| anyhow!("unknown sample rate"))?;
+        let mut report = DecodeReport {
+            expected_frames: codec_params.n_frames,
+            ..Default::default()
+        };
+        let limit = Self::max_decode_frames();
+        if max_seconds.is_none() && report.expected_frames.is_some_and(|n| n > limit) {
+            bail!("audio declares more than the decode limit of {limit} frames");
+        }
+        let max_frames =
+            max_seconds.map(|seconds| (seconds.max(0.) * sample_rate as f64) as usize);
+        let mut skipped_packets = 0;
         let mut decoder = codecs.make(codec_params, &Default::default())?;
         let mut frames = Vec::new();
         loop {
+            if max_frames.is_some_and(|max| frames.len() >= max) {
+                break;
+            }
             match format_reader.next_packet() {
                 Ok(packet) => {
                     let buffer = match decoder.decode(&packet) {
@@ -101,11 +323,23 @@ impl AudioClip {
                         Err(symphonia::core::errors::Error::DecodeError(s))
                             if s.contains("invalid main_data offset") =>
                         {
+                            skipped_packets += 1;
                             continue;
                         }
                         Err(err) => return Err(err.into()),
                     };
-                    load_frames_from_buffer_ref(&mut frames, &buffer)?;
+                    let needed = frames.len() + buffer.frames();
+                    let within_preview = max_frames.is_some_and(|max| max as u64 <= limit);
+                    if needed as u64 > limit && !within_preview {
+                        bail!("audio exceeds the decode limit of {limit} frames");
+                    }
+                    if needed > frames.capacity() {
+                        // Grow geometrically, but never reserve past the limit.
+                        let cap = usize::try_from(limit).unwrap_or(usize::MAX).max(needed);
+                        let target = (frames.capacity() * 2).clamp(needed, cap);
+                        frames.reserve_exact(target - frames.len());
+                    }
+                    load_frames_from_buffer_ref(&mut frames, &buffer, downmix)?;
                 }
                 Err(error) => match error {
                     symphonia::core::errors::Error::IoError(error)
@@ -117,52 +351,430 @@ impl AudioClip {
                 },
             }
         }
-        Ok((frames, sample_rate))
+        if let Some(max) = max_frames {
+            frames.truncate(max);
+        }
+        report.decoded_frames = frames.len() as u64;
+        if skipped_packets > 0 {
+            report
+                .warnings
+                .push(format!("skipped {skipped_packets} undecodable packets"));
+        }
+        if let (Some(expected), None) = (report.expected_frames, max_frames) {
+            if report.decoded_frames < expected {
+                report.warnings.push(format!(
+                    "decoded {} of {expected} frames, the data may be truncated",
+                    report.decoded_frames
+                ));
+            }
+        }
+        Ok((frames, sample_rate, report))
+    }
+
+    /// Caps how many frames decoding may produce, so a malformed or hostile
+    /// file fails instead of exhausting memory. Applies process-wide to every
+    /// decode, including the C API. Defaults to [`DEFAULT_MAX_DECODE_FRAMES`].
+    pub fn set_max_decode_frames(max: u64) {
+        MAX_DECODE_FRAMES.store(max, Ordering::Relaxed);
+    }
+
+    pub fn max_decode_frames() -> u64 {
+        MAX_DECODE_FRAMES.load(Ordering::Relaxed)
+    }
+
+    /// Builds Symphonia's codec and format registries and decodes a few
+    /// silent frames of `format`, so the first real decode doesn't pay for
+    /// that setup. Call it at startup for each format the app loads, ideally
+    /// off the main thread.
+    pub fn warm_decoder(format: WarmFormat) {
+        let _ = Self::decode(format.silence());
     }
 
+    /// Decodes `data`, folding sources with more than two channels to stereo
+    /// with [`DownmixMode::Itu`].
     #[inline]
     pub fn new(data: Vec<u8>) -> Result<Self> {
-        let (frames, sample_rate) = Self::decode(data)?;
+        Self::new_with_downmix(data, DownmixMode::default())
+    }
+
+    pub fn new_with_downmix(data: Vec<u8>, downmix: DownmixMode) -> Result<Self> {
+        let (frames, sample_rate, report) = Self::decode_with_report(data, downmix, None)?;
+        Ok(Self::with_report(frames, sample_rate, Some(report)))
+    }
+
+    /// Decodes only the first `max_seconds` of `data`, e.g. to audition long
+    /// tracks in an asset browser without paying for a full decode.
+    pub fn preview(data: Vec<u8>, max_seconds: f64) -> Result<Self> {
+        let (frames, sample_rate, _) =
+            Self::decode_with_report(data, DownmixMode::default(), Some(max_seconds))?;
         Ok(Self::from_raw(frames, sample_rate))
     }
 
+    /// Diagnostics from decoding this clip, `None` for clips built from raw
+    /// frames or derived from another clip.
+    pub fn decode_report(&self) -> Option<&DecodeReport> {
+        self.1.report.as_ref()
+    }
+
+    /// Shorthand for the warnings of [`AudioClip::decode_report`]; empty when
+    /// the clip decoded cleanly.
+    pub fn decode_warnings(&self) -> Vec<String> {
+        self.decode_report()
+            .map_or_else(Vec::new, |report| report.warnings.clone())
+    }
+
+    /// Reads the clip at `position` seconds. Positions are converted with the
+    /// clip's own sample rate, so renderers advancing by `1 / output_rate`
+    /// play clips of any rate at the right speed.
+    #[inline]
     pub fn sample(&self, position: f64) -> Option<Frame> {
-        let position = position * self.0.sample_rate as f64;
+        self.sample_with(position, ResampleQuality::Linear)
+    }
+
+    pub fn sample_with(&self, position: f64, quality: ResampleQuality) -> Option<Frame> {
+        Some(self.interpolate(position * self.1.sample_rate as f64, quality)? * self.gain())
+    }
+
+    /// Reads the raw frames at a fractional frame `position`, without gain.
+    fn interpolate(&self, position: f64, quality: ResampleQuality) -> Option<Frame> {
+        let frames = &self.1.frames;
         let actual_index = position as usize;
-        if let Some(frame) = self.0.frames.get(actual_index) {
-            let next_frame = self.0.frames.get(actual_index + 1).unwrap_or(frame);
-            Some(frame.interpolate(next_frame, (position - actual_index as f64) as f32))
-        } else {
-            None
+        let frame = frames.get(actual_index)?;
+        let f = (position - actual_index as f64) as f32;
+        let at = |offset: isize| {
+            frames[(actual_index as isize + offset).clamp(0, frames.len() as isize - 1) as usize]
+        };
+        let result = match quality {
+            ResampleQuality::Linear => {
+                let next_frame = frames.get(actual_index + 1).unwrap_or(frame);
+                frame.interpolate(next_frame, f)
+            }
+            ResampleQuality::Cubic => {
+                let (p0, p2, p3) = (at(-1), at(1), at(2));
+                Frame(
+                    catmull_rom(p0.0, frame.0, p2.0, p3.0, f),
+                    catmull_rom(p0.1, frame.1, p2.1, p3.1, f),
+                )
+            }
+            ResampleQuality::Sinc => (1 - SINC_HALF_WIDTH..=SINC_HALF_WIDTH)
+                .fold(Frame::default(), |acc, offset| {
+                    acc + at(offset) * windowed_sinc(offset as f32 - f)
+                }),
+            ResampleQuality::NearestNeighbor => at((f >= 0.5) as isize),
+        };
+        Some(result)
+    }
+
+    /// Returns a copy of this clip converted to `sample_rate`, keeping its gain.
+    pub fn resample(&self, sample_rate: u32, quality: ResampleQuality) -> AudioClip {
+        if sample_rate == self.sample_rate() {
+            return self.clone();
         }
+        let ratio = self.sample_rate() as f64 / sample_rate as f64;
+        let len = (self.frame_count() as f64 / ratio).ceil() as usize;
+        let frames = (0..len)
+            .filter_map(|index| self.interpolate(index as f64 * ratio, quality))
+            .collect();
+        let mut clip = Self::from_raw(frames, sample_rate);
+        clip.set_gain(self.gain());
+        clip
+    }
+
+    /// Removes leading and trailing frames whose channels are both below
+    /// `threshold_db` dBFS, ignoring the gain. A fully silent clip becomes
+    /// empty. Existing clones keep the untrimmed frames.
+    pub fn trim_silence(&mut self, threshold_db: f32) {
+        let threshold = 10f32.powf(threshold_db / 20.);
+        let audible = |frame: &Frame| frame.0.abs() >= threshold || frame.1.abs() >= threshold;
+        let frames = self.frames();
+        let start = frames.iter().position(audible).unwrap_or(frames.len());
+        let end = frames
+            .iter()
+            .rposition(audible)
+            .map_or(start, |index| index + 1);
+        let mut clip = Self::from_raw(frames[start..end].to_vec(), self.sample_rate());
+        clip.set_gain(self.gain());
+        *self = clip;
+    }
+
+    /// Bakes linear fades of `fade_in` and `fade_out` seconds into the edges
+    /// of the frames. Existing clones keep the original frames.
+    pub fn apply_fades(&mut self, fade_in: f64, fade_out: f64) {
+        let mut frames = self.frames().to_vec();
+        let len = frames.len();
+        let fade_in = ((fade_in * self.sample_rate() as f64) as usize).min(len);
+        let fade_out = ((fade_out * self.sample_rate() as f64) as usize).min(len);
+        for (index, frame) in frames[..fade_in].iter_mut().enumerate() {
+            *frame = *frame * (index as f32 / fade_in as f32);
+        }
+        for (index, frame) in frames[len - fade_out..].iter_mut().rev().enumerate() {
+            *frame = *frame * (index as f32 / fade_out as f32);
+        }
+        let mut clip = Self::from_raw(frames, self.sample_rate());
+        clip.set_gain(self.gain());
+        *self = clip;
+    }
+
+    /// Collapses both channels to their average, so the clip sits exactly
+    /// in the centre. Existing clones keep the original frames.
+    pub fn to_mono(&mut self) {
+        let frames = self
+            .frames()
+            .iter()
+            .map(|frame| {
+                let mono = frame.avg();
+                Frame(mono, mono)
+            })
+            .collect();
+        let mut clip = Self::from_raw(frames, self.sample_rate());
+        clip.set_gain(self.gain());
+        *self = clip;
+    }
+
+    /// Joins `clips` end to end into one clip, overlapping each adjacent pair
+    /// by `fade` seconds with an equal-power crossfade. The overlap is
+    /// shortened where a clip is shorter than `fade`.
+    ///
+    /// Each clip's gain is baked into the result, whose own gain is 1. All
+    /// clips must share a sample rate.
+    pub fn crossfade_concat(clips: &[AudioClip], fade: f64) -> Result<AudioClip> {
+        let Some(first) = clips.first() else {
+            bail!("no clips to concatenate");
+        };
+        let sample_rate = first.sample_rate();
+        if let Some(clip) = clips.iter().find(|clip| clip.sample_rate() != sample_rate) {
+            bail!(
+                "sample rate mismatch: {} Hz and {} Hz",
+                sample_rate,
+                clip.sample_rate()
+            );
+        }
+        let fade = (fade.max(0.) * sample_rate as f64) as usize;
+        let mut frames: Vec<Frame> =
+            Vec::with_capacity(clips.iter().map(|clip| clip.frame_count()).sum());
+        for clip in clips {
+            let gain = clip.gain();
+            let overlap = fade.min(frames.len()).min(clip.frame_count());
+            let start = frames.len() - overlap;
+            for (index, (output, frame)) in
+                frames[start..].iter_mut().zip(clip.frames()).enumerate()
+            {
+                let t = (index as f32 + 0.5) / overlap as f32 * std::f32::consts::FRAC_PI_2;
+                *output = *output * t.cos() + *frame * (gain * t.sin());
+            }
+            frames.extend(clip.frames()[overlap..].iter().map(|frame| *frame * gain));
+        }
+        Ok(Self::from_raw(frames, sample_rate))
+    }
+
+    /// Sets a non-destructive trim applied wherever the clip is played.
+    ///
+    /// The gain is shared by all clones of this clip, including ones already
+    /// handed to a `Sfx` or `Music`.
+    pub fn set_gain(&mut self, gain: f32) {
+        self.1.gain.store(gain, Ordering::Relaxed);
+    }
+
+    #[inline(always)]
+    pub fn gain(&self) -> f32 {
+        self.1.gain.load(Ordering::Relaxed)
     }
 
     #[inline(always)]
     pub fn frames(&self) -> &[Frame] {
-        &self.0.frames
+        &self.1.frames
+    }
+
+    /// Min/max sample value (across both channels) for each of `buckets` equal slices of the clip.
+    pub fn peaks(&self, buckets: usize) -> Vec<(f32, f32)> {
+        let frames = self.frames();
+        (0..buckets)
+            .map(|bucket| {
+                let start = bucket * frames.len() / buckets;
+                let end = (bucket + 1) * frames.len() / buckets;
+                frames[start..end]
+                    .iter()
+                    .fold(None, |acc: Option<(f32, f32)>, frame| {
+                        let (min, max) = (frame.0.min(frame.1), frame.0.max(frame.1));
+                        Some(acc.map_or((min, max), |(lo, hi)| (lo.min(min), hi.max(max))))
+                    })
+                    .unwrap_or_default()
+            })
+            .collect()
     }
 
     #[inline(always)]
     pub fn to_vec(&self) -> Vec<f32> {
         let mut vec = Vec::new();
-        for i in self.0.frames.iter() {
+        for i in self.1.frames.iter() {
             vec.push(i.0);
             vec.push(i.1);
         }
         vec
     }
 
+    /// Serializes the clip, with its gain applied, as a stereo 32-bit float WAV.
+    pub fn to_wav_bytes(&self) -> Vec<u8> {
+        const CHANNELS: u16 = 2;
+        const BYTES_PER_SAMPLE: u16 = 4;
+        let block_align = CHANNELS * BYTES_PER_SAMPLE;
+        let data_len = (self.frame_count() * block_align as usize) as u32;
+        let mut bytes = Vec::with_capacity(44 + data_len as usize);
+        bytes.extend_from_slice(b"RIFF");
+        bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+        bytes.extend_from_slice(b"WAVEfmt ");
+        bytes.extend_from_slice(&16u32.to_le_bytes());
+        bytes.extend_from_slice(&3u16.to_le_bytes()); // IEEE float
+        bytes.extend_from_slice(&CHANNELS.to_le_bytes());
+        bytes.extend_from_slice(&self.sample_rate().to_le_bytes());
+        bytes.extend_from_slice(&(self.sample_rate() * block_align as u32).to_le_bytes());
+        bytes.extend_from_slice(&block_align.to_le_bytes());
+        bytes.extend_from_slice(&(BYTES_PER_SAMPLE * 8).to_le_bytes());
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&data_len.to_le_bytes());
+        let gain = self.gain();
+        for frame in self.1.frames.iter() {
+            bytes.extend_from_slice(&(frame.0 * gain).to_le_bytes());
+            bytes.extend_from_slice(&(frame.1 * gain).to_le_bytes());
+        }
+        bytes
+    }
+
+    pub fn write_wav(&self, path: impl AsRef<Path>) -> Result<()> {
+        std::fs::write(path, self.to_wav_bytes())?;
+        Ok(())
+    }
+
     #[inline(always)]
     pub fn sample_rate(&self) -> u32 {
-        self.0.sample_rate
+        self.1.sample_rate
     }
 
     #[inline(always)]
     pub fn frame_count(&self) -> usize {
-        self.0.frames.len()
+        self.1.frames.len()
+    }
+
+    /// Heap memory held by the decoded frames. Clones share the same buffer,
+    /// so count each distinct clip once.
+    pub fn memory_bytes(&self) -> usize {
+        std::mem::size_of::<ClipInner>() + self.1.frames.capacity() * std::mem::size_of::<Frame>()
+    }
+
+    /// Hash of the sample rate and decoded frames, ignoring the gain, to
+    /// deduplicate the same audio loaded from different paths. Stable within
+    /// a process only; don't persist it.
+    pub fn content_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.sample_rate().hash(&mut hasher);
+        for frame in self.frames() {
+            hasher.write_u32(frame.0.to_bits());
+            hasher.write_u32(frame.1.to_bits());
+        }
+        hasher.finish()
+    }
+
+    /// Exact length in frames, for scheduling without rounding through seconds.
+    #[inline(always)]
+    pub fn length_frames(&self) -> u64 {
+        self.frame_count() as u64
     }
 
     pub fn length(&self) -> f64 {
         self.frame_count() as f64 / self.sample_rate() as f64
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VALUES: [f64; 4] = [0.5, -0.25, 0.999, -1.];
+
+    /// A stereo 48 kHz WAV whose left channel holds `VALUES` and right
+    /// channel their negation, encoded by `encode`.
+    fn wav(format: u16, bits: u16, encode: impl Fn(f64) -> Vec<u8>) -> Vec<u8> {
+        let mut samples = Vec::new();
+        for value in VALUES {
+            samples.extend(encode(value));
+            samples.extend(encode(-value));
+        }
+        let block_align = 2 * bits / 8;
+        let mut data = Vec::new();
+        data.extend(b"RIFF");
+        data.extend((36 + samples.len() as u32).to_le_bytes());
+        data.extend(b"WAVEfmt ");
+        data.extend(16u32.to_le_bytes());
+        data.extend(format.to_le_bytes());
+        data.extend(2u16.to_le_bytes());
+        data.extend(48000u32.to_le_bytes());
+        data.extend((48000 * block_align as u32).to_le_bytes());
+        data.extend(block_align.to_le_bytes());
+        data.extend(bits.to_le_bytes());
+        data.extend(b"data");
+        data.extend((samples.len() as u32).to_le_bytes());
+        data.extend(samples);
+        data
+    }
+
+    fn assert_decodes(data: Vec<u8>, tolerance: f32) {
+        let (frames, sample_rate) = AudioClip::decode(data).unwrap();
+        assert_eq!(sample_rate, 48000);
+        assert_eq!(frames.len(), VALUES.len());
+        for (frame, value) in frames.iter().zip(VALUES) {
+            let value = value as f32;
+            assert!(
+                (frame.0 - value).abs() <= tolerance,
+                "{} != {value}",
+                frame.0
+            );
+            assert!(
+                (frame.1 + value).abs() <= tolerance,
+                "{} != {}",
+                frame.1,
+                -value
+            );
+        }
+    }
+
+    /// Signed PCM with `bits` bits, clamped like an encoder would.
+    fn int(bits: u32) -> impl Fn(f64) -> Vec<u8> {
+        move |value| {
+            let scale = (1i64 << (bits - 1)) as f64;
+            let sample = (value * scale).round().clamp(-scale, scale - 1.) as i64;
+            sample.to_le_bytes()[..bits as usize / 8].to_vec()
+        }
+    }
+
+    #[test]
+    fn decodes_s24() {
+        assert_decodes(wav(1, 24, int(24)), 1. / (1 << 23) as f32);
+    }
+
+    #[test]
+    fn decodes_s32() {
+        assert_decodes(wav(1, 32, int(32)), 1. / (1u32 << 31) as f32);
+    }
+
+    #[test]
+    fn decodes_f32() {
+        assert_decodes(
+            wav(3, 32, |value| (value as f32).to_le_bytes().to_vec()),
+            0.,
+        );
+    }
+
+    #[test]
+    fn decodes_f64() {
+        assert_decodes(wav(3, 64, |value| value.to_le_bytes().to_vec()), 0.);
+    }
+
+    #[test]
+    fn warm_up_silence_decodes() {
+        for format in [WarmFormat::Wav, WarmFormat::Mp3, WarmFormat::Aac] {
+            let (frames, _) = AudioClip::decode(format.silence()).unwrap();
+            assert!(!frames.is_empty(), "{format:?}");
+            assert!(frames.iter().all(|frame| frame.0 == 0. && frame.1 == 0.));
+        }
+    }
+}
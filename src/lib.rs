@@ -1,24 +1,52 @@
 /// Simple And Stupid Audio for Rust, optimized for low latency.
 pub mod backend;
 use atomic_float::AtomicF64;
-pub use backend::Backend;
+pub use backend::{Backend, BackendInfo};
+
+mod bus;
+pub use bus::{BusEffect, BusId};
 
 mod clip;
-pub use clip::AudioClip;
+pub use clip::{
+    AudioClip, DecodeReport, DownmixMode, ResampleQuality, WarmFormat, DEFAULT_MAX_DECODE_FRAMES,
+};
+
+mod log;
+pub use log::{LogCallback, LogLevel};
+
+mod loudness;
+pub use loudness::LoudnessStats;
 
 mod mixer;
+pub use mixer::MixMode;
+
+mod ramp;
+
+mod tag;
 
 mod renderer;
-pub use renderer::{Music, MusicParams, PlaySfxParams, Renderer, Sfx};
+pub use renderer::{
+    LayeredMusic, LayeredMusicParams, Music, MusicParams, MusicState, PlayResult, PlaySfxParams,
+    PushSource, Renderer, RendererId, Sfx, SfxParams, UnderrunFill, VoiceId,
+};
+#[cfg(feature = "async")]
+pub use renderer::Finished;
 
-use crate::{backend::BackendSetup, mixer::MixerCommand};
-use anyhow::{anyhow, Context, Result};
+use crate::{
+    backend::BackendSetup,
+    loudness::LoudnessMeter,
+    mixer::{MixerCommand, MixerState},
+    ramp::seconds_to_frames,
+    tag::{checked, ManagerTag, Tagged},
+};
+use anyhow::{anyhow, bail, Context, Result};
 use ringbuf::{HeapProducer, HeapRb};
 use std::{
-    ffi::{c_char, CStr}, ops::{Add, Mul}, slice, sync::{
+    any::Any, cell::RefCell, ffi::{c_char, CStr, CString}, ops::{Add, Mul}, ptr, slice, sync::{
         atomic::Ordering,
         Arc,
-    }
+    },
+    time::{Duration, Instant},
 };
 
 fn buffer_is_full<E>(_: E) -> anyhow::Error {
@@ -56,6 +84,13 @@ impl Mul<f32> for Frame {
 
 const LATENCY_RECORD_NUM: usize = 640;
 
+/// How long [`AudioManager::flush`] waits for the audio thread.
+pub const FLUSH_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// How long `create_audio_manager` keeps retrying while no backend starts.
+/// Use `create_audio_manager_with_retry` to pick another timeout.
+pub const INIT_RETRY_TIMEOUT: Duration = Duration::from_millis(300);
+
 pub struct LatencyRecorder {
     records: [f64; LATENCY_RECORD_NUM],
     head: usize,
@@ -96,10 +131,56 @@ impl LatencyRecorder {
     }
 }
 
+/// Changes reported by [`AudioManager::poll_event`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioEvent {
+    /// The backend switched to a new output sample rate mid-stream, e.g. when
+    /// a Bluetooth headset changes profile. Renderers follow automatically;
+    /// clips from [`AudioManager::prepare_clip`] should be prepared again.
+    SampleRateChanged(u32),
+}
+
+#[derive(Debug, Clone)]
+pub struct AudioManagerConfig {
+    /// Seconds over which the output ramps up from silence after the stream
+    /// starts or recovers. `0` disables the ramp.
+    pub startup_fade: f64,
+    /// Fixed number of frames the mixer processes at a time, regardless of
+    /// the backend's buffer size. `None` renders each request in one go.
+    pub mixer_block_size: Option<usize>,
+}
+impl Default for AudioManagerConfig {
+    fn default() -> Self {
+        Self {
+            startup_fade: 0.,
+            mixer_block_size: None,
+        }
+    }
+}
+
+#[repr(C)]
 pub struct AudioManager {
+    /// First, where the C API reads it.
+    tag: ManagerTag,
     backend: Box<dyn Backend>,
     latency: Arc<AtomicF64>,
+    mixer_state: Arc<MixerState>,
     prod: HeapProducer<MixerCommand>,
+    sent_commands: u64,
+    next_renderer_id: u64,
+    next_bus_id: u64,
+    /// Set by [`AudioManager::stop`] so recovery doesn't restart the stream.
+    stopped: bool,
+    /// Sample rate last reported through [`AudioManager::poll_event`].
+    known_sample_rate: u32,
+}
+
+impl Tagged for AudioManager {
+    const MAGIC: u32 = ManagerTag::MAGIC;
+
+    unsafe fn tag_ptr(ptr: *const Self) -> *const u32 {
+        ptr::addr_of!((*ptr).tag).cast()
+    }
 }
 
 impl AudioManager {
@@ -107,46 +188,424 @@ impl AudioManager {
         Self::new_box(Box::new(backend))
     }
 
-    pub fn new_box(mut backend: Box<dyn Backend>) -> Result<Self> {
+    pub fn new_box(backend: Box<dyn Backend>) -> Result<Self> {
+        Self::with_config(backend, AudioManagerConfig::default())
+    }
+
+    pub fn with_config(mut backend: Box<dyn Backend>, config: AudioManagerConfig) -> Result<Self> {
         let (prod, cons) = HeapRb::new(16).split();
         let latency: Arc<AtomicF64> = Arc::default();
         let latency_rec = LatencyRecorder::new(Arc::clone(&latency));
+        let mixer_state = Arc::new(MixerState {
+            startup_fade: config.startup_fade,
+            block_size: config.mixer_block_size.unwrap_or(0),
+            ..Default::default()
+        });
         backend.setup(BackendSetup {
             mixer_cons: cons,
             latency_rec,
+            mixer_state: Arc::clone(&mixer_state),
         })?;
+        mixer_state.started.store(true, Ordering::Relaxed);
         backend.start()?;
         Ok(Self {
+            tag: ManagerTag::new(),
             backend,
             latency,
             prod,
+            sent_commands: 0,
+            next_renderer_id: 0,
+            next_bus_id: 0,
+            stopped: false,
+            known_sample_rate: mixer_state.sample_rate.load(Ordering::Relaxed),
+            mixer_state,
         })
     }
 
+    /// Tries each backend in order, returning a manager for the first one that starts.
+    pub fn new_with_fallback(backends: Vec<Box<dyn Backend>>) -> Result<Self> {
+        let mut last_error = anyhow!("no backend available");
+        for backend in backends {
+            match Self::new_box(backend) {
+                Ok(manager) => return Ok(manager),
+                Err(err) => last_error = err,
+            }
+        }
+        Err(last_error)
+    }
+
+    /// Like [`AudioManager::new_with_fallback`], but retries with backoff for up
+    /// to `timeout` when every backend fails, e.g. while a freshly connected
+    /// output device is not ready yet. `backends` builds a fresh list per attempt.
+    pub fn new_with_retry(
+        mut backends: impl FnMut() -> Vec<Box<dyn Backend>>,
+        timeout: Duration,
+    ) -> Result<Self> {
+        let deadline = Instant::now() + timeout;
+        let mut backoff = Duration::from_millis(20);
+        loop {
+            let error = match Self::new_with_fallback(backends()) {
+                Ok(manager) => return Ok(manager),
+                Err(err) => err,
+            };
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(error);
+            }
+            std::thread::sleep(backoff.min(deadline - now));
+            backoff *= 2;
+        }
+    }
+
+    /// `buffer_size`, 64 by default, bounds both the queued commands and the
+    /// voices sounding at once; beyond that a voice is stolen, see [`Sfx::play`].
     pub fn create_sfx(&mut self, clip: AudioClip, buffer_size: Option<usize>) -> Result<Sfx> {
-        let (sfx, sfx_renderer) = Sfx::new(clip, buffer_size);
-        self.add_renderer(sfx_renderer)?;
+        self.create_sfx_with_params(
+            clip,
+            SfxParams {
+                buffer_size,
+                ..Default::default()
+            },
+        )
+    }
+
+    pub fn create_sfx_with_params(&mut self, clip: AudioClip, params: SfxParams) -> Result<Sfx> {
+        let id = self.next_renderer_id();
+        let (sfx, sfx_renderer) = Sfx::new(id, clip, params.buffer_size);
+        self.add_renderer_with_id(id, Box::new(sfx_renderer), params.bypass_master)?;
         Ok(sfx)
     }
 
+    /// Plays `clip` once on a transient Sfx that is removed when it finishes.
+    pub fn play_oneshot(&mut self, clip: AudioClip, params: PlaySfxParams) -> Result<()> {
+        let id = self.next_renderer_id();
+        let (mut sfx, sfx_renderer) = Sfx::new(id, clip, Some(1));
+        sfx.play(params);
+        self.add_renderer_with_id(id, Box::new(sfx_renderer), false)
+    }
+
     pub fn create_music(&mut self, clip: AudioClip, settings: MusicParams) -> Result<Music> {
-        let (music, music_renderer) = Music::new(clip, settings);
-        self.add_renderer(music_renderer)?;
+        let id = self.next_renderer_id();
+        let bypass_master = settings.bypass_master;
+        let (music, music_renderer) = Music::new(id, clip, settings, Arc::clone(&self.mixer_state));
+        self.add_renderer_with_id(id, Box::new(music_renderer), bypass_master)?;
         Ok(music)
     }
 
-    pub fn add_renderer(&mut self, renderer: impl Renderer + 'static) -> Result<()> {
+    /// Creates music from stems of equal length that play in lockstep, with
+    /// per-layer gains. All layers must share a sample rate.
+    pub fn create_layered_music(
+        &mut self,
+        layers: Vec<AudioClip>,
+        settings: LayeredMusicParams,
+    ) -> Result<LayeredMusic> {
+        let id = self.next_renderer_id();
+        let bypass_master = settings.bypass_master;
+        let (music, music_renderer) = LayeredMusic::new(id, layers, settings)?;
+        self.add_renderer_with_id(id, Box::new(music_renderer), bypass_master)?;
+        Ok(music)
+    }
+
+    /// Creates a source the host feeds with frames, holding up to `capacity` of them.
+    pub fn create_push_source(&mut self, capacity: usize) -> Result<PushSource> {
+        let id = self.next_renderer_id();
+        let (source, source_renderer) = PushSource::new(id, capacity);
+        self.add_renderer_with_id(id, Box::new(source_renderer), false)?;
+        Ok(source)
+    }
+
+    fn next_renderer_id(&mut self) -> RendererId {
+        let id = RendererId(self.next_renderer_id);
+        self.next_renderer_id += 1;
+        id
+    }
+
+    fn add_renderer_with_id(
+        &mut self,
+        id: RendererId,
+        renderer: Box<dyn Renderer>,
+        bypass_master: bool,
+    ) -> Result<()> {
+        self.send(
+            MixerCommand::AddRenderer(id, renderer, bypass_master),
+            "add renderer",
+        )
+    }
+
+    fn send(&mut self, cmd: MixerCommand, context: &'static str) -> Result<()> {
         self.prod
-            .push(MixerCommand::AddRenderer(Box::new(renderer)))
+            .push(cmd)
             .map_err(buffer_is_full)
-            .context("add renderer")?;
+            .context(context)?;
+        self.sent_commands += 1;
+        Ok(())
+    }
+
+    pub fn add_renderer(&mut self, renderer: impl Renderer + 'static) -> Result<RendererId> {
+        let id = self.next_renderer_id();
+        self.add_renderer_with_id(id, Box::new(renderer), false)?;
+        Ok(id)
+    }
+
+    /// Adds an effect bus, e.g. a shared reverb. Renderers feed it through
+    /// [`AudioManager::set_send`] and its output is mixed into the master.
+    pub fn create_bus(&mut self, effect: impl BusEffect + 'static) -> Result<BusId> {
+        let id = BusId(self.next_bus_id);
+        self.next_bus_id += 1;
+        self.send(MixerCommand::AddBus(id, Box::new(effect)), "create bus")?;
+        Ok(id)
+    }
+
+    /// Drops a bus and its effect. Sends to it are ignored from then on.
+    pub fn remove_bus(&mut self, id: BusId) -> Result<()> {
+        self.send(MixerCommand::RemoveBus(id), "remove bus")
+    }
+
+    /// Sends a renderer's output to `bus` at `level`, in addition to its dry
+    /// signal. A level of `0` removes the send.
+    pub fn set_send(&mut self, id: RendererId, bus: BusId, level: f32) -> Result<()> {
+        self.send(MixerCommand::SetSend(id, bus, level), "set send")
+    }
+
+    /// Excludes a renderer from master volume and mute, e.g. for feedback
+    /// sounds that must stay audible when the user turns everything down.
+    /// Its output still counts towards [`AudioManager::clipping_detected`].
+    ///
+    /// [`MusicParams`], [`LayeredMusicParams`] and [`SfxParams`] set this at
+    /// creation, so the first block already bypasses the master.
+    pub fn set_bypass_master(&mut self, id: RendererId, bypass: bool) -> Result<()> {
+        self.send(MixerCommand::SetBypassMaster(id, bypass), "set bypass master")
+    }
+
+    /// Caps the number of simultaneously sounding renderers. When exceeded,
+    /// the lowest-priority, then quietest, then oldest renderers are stopped:
+    /// music is paused and sfx voices are cut, as if they had finished.
+    /// Renderers that can't be stopped, such as a [`PushSource`], keep playing.
+    ///
+    /// While a cap is set, each renderer is mixed separately to measure its
+    /// level, which costs a little extra CPU.
+    pub fn set_max_active(&mut self, max: usize) -> Result<()> {
+        self.send(MixerCommand::SetMaxActive(max), "set max active")
+    }
+
+    /// Fades out, stops and removes every renderer added so far, leaving the
+    /// mixer empty. Existing handles stay valid but no longer have any effect.
+    pub fn clear_all(&mut self) -> Result<()> {
+        self.send(MixerCommand::ClearAll, "clear all")
+    }
+
+    /// Schedules master gain breakpoints as `(seconds from now, gain)`,
+    /// interpolated linearly on the mixer's sample clock and multiplied with
+    /// the master volume. The gain ramps from its current value to the first
+    /// breakpoint and holds the last one; automate to `[(0., 1.)]` to restore
+    /// it. Replaces any automation still in progress.
+    pub fn automate_master_volume(&mut self, mut points: Vec<(f64, f32)>) -> Result<()> {
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+        self.send(
+            MixerCommand::AutomateMasterVolume(points),
+            "automate master volume",
+        )
+    }
+
+    /// Enables a ~20 Hz high-pass on the master output that removes DC
+    /// offset accumulated from the sources. Off by default.
+    pub fn set_dc_block(&mut self, enabled: bool) -> Result<()> {
+        self.send(MixerCommand::SetDcBlock(enabled), "set dc block")
+    }
+
+    /// Number of renderers that were sounding in the last rendered block.
+    pub fn active_renderers(&self) -> usize {
+        self.mixer_state.active_renderers.load(Ordering::Relaxed)
+    }
+
+    /// Routes a renderer to the given left/right channels on backends with
+    /// more than two output channels. Defaults to `(0, 1)`.
+    pub fn set_output_channels(&mut self, id: RendererId, channels: (usize, usize)) -> Result<()> {
+        self.send(
+            MixerCommand::SetOutputChannels(id, channels),
+            "set output channels",
+        )
+    }
+
+    /// Outputs only the given renderer, or everything again with `None`.
+    ///
+    /// Muted renderers keep advancing, so they stay in sync when solo is cleared.
+    pub fn solo(&mut self, id: Option<RendererId>) -> Result<()> {
+        self.send(MixerCommand::Solo(id), "solo")
+    }
+
+    pub fn set_mix_mode(&mut self, mode: MixMode) -> Result<()> {
+        self.send(MixerCommand::SetMixMode(mode), "set mix mode")
+    }
+
+    /// Blocks until the mixer has processed every command sent so far.
+    ///
+    /// Fails if the audio thread doesn't catch up within [`FLUSH_TIMEOUT`],
+    /// e.g. because the stream isn't running.
+    pub fn flush(&mut self) -> Result<()> {
+        let deadline = Instant::now() + FLUSH_TIMEOUT;
+        while self
+            .mixer_state
+            .processed_commands
+            .load(Ordering::Acquire)
+            < self.sent_commands
+        {
+            if Instant::now() >= deadline {
+                bail!("timed out waiting for the mixer to process commands");
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
         Ok(())
     }
 
+    /// Output sample rate negotiated by the backend, or `None` before the stream has started.
+    pub fn sample_rate(&self) -> Option<u32> {
+        match self.mixer_state.sample_rate.load(Ordering::Relaxed) {
+            0 => None,
+            sample_rate => Some(sample_rate),
+        }
+    }
+
+    /// Returns the next change the audio thread has published since the last
+    /// call, if any. Call this periodically, e.g. once per game frame.
+    pub fn poll_event(&mut self) -> Option<AudioEvent> {
+        let sample_rate = self.mixer_state.sample_rate.load(Ordering::Relaxed);
+        if sample_rate == self.known_sample_rate {
+            return None;
+        }
+        let previous = std::mem::replace(&mut self.known_sample_rate, sample_rate);
+        // The first rate a backend negotiates is not a change.
+        (previous != 0).then_some(AudioEvent::SampleRateChanged(sample_rate))
+    }
+
+    /// Resamples `clip` to the output sample rate once, so playback needs no
+    /// per-frame rate conversion. Returns the clip unchanged if the rate is
+    /// not known yet.
+    pub fn prepare_clip(&self, clip: &AudioClip) -> AudioClip {
+        match self.sample_rate() {
+            Some(sample_rate) => clip.resample(sample_rate, ResampleQuality::Sinc),
+            None => clip.clone(),
+        }
+    }
+
+    /// Output latency in seconds, averaged over the backend's recent
+    /// callback timestamps.
     pub fn estimate_latency(&self) -> f64 {
         self.latency.load(Ordering::SeqCst)
     }
 
+    /// [`AudioManager::estimate_latency`] in output frames at the negotiated
+    /// sample rate. `0` until the rate is known.
+    pub fn estimated_latency_frames(&self) -> u64 {
+        let sample_rate = self.mixer_state.sample_rate.load(Ordering::Relaxed);
+        seconds_to_frames(self.estimate_latency(), sample_rate) as u64
+    }
+
+    /// Latency in seconds added by the backend's output buffer, derived from
+    /// the size of the last block it requested. `0` until audio has rendered.
+    pub fn output_buffer_latency(&self) -> f64 {
+        let sample_rate = self.mixer_state.sample_rate.load(Ordering::Relaxed);
+        if sample_rate == 0 {
+            return 0.;
+        }
+        self.mixer_state.block_frames.load(Ordering::Relaxed) as f64 / sample_rate as f64
+    }
+
+    /// Starts or stops BS.1770 loudness metering of the master output.
+    /// Enabling restarts the integrated measurement. Surround outputs are
+    /// weighted assuming the WAVE channel order (L, R, C, LFE, ...).
+    pub fn set_loudness_metering(&mut self, enabled: bool) -> Result<()> {
+        let meter = enabled
+            .then(|| Box::new(LoudnessMeter::new(self.sample_rate().unwrap_or(48000))));
+        self.send(MixerCommand::SetLoudnessMeter(meter), "set loudness metering")
+    }
+
+    /// Latest master loudness, see [`AudioManager::set_loudness_metering`].
+    pub fn loudness(&self) -> LoudnessStats {
+        let state = &self.mixer_state;
+        LoudnessStats {
+            momentary: state.momentary_loudness.load(Ordering::Relaxed),
+            short_term: state.short_term_loudness.load(Ordering::Relaxed),
+            integrated: state.integrated_loudness.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Starts or stops the band analysis behind
+    /// [`AudioManager::spectrum_bands`]. Off by default.
+    pub fn set_spectrum_metering(&self, enabled: bool) {
+        let state = &self.mixer_state;
+        state.band_metering.store(enabled, Ordering::Relaxed);
+        if !enabled {
+            for band in &state.bands {
+                band.store(0., Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// RMS of the master output's low (< 250 Hz), mid and high (> 4 kHz)
+    /// bands over the last rendered block, e.g. for a visualizer.
+    ///
+    /// All `0` unless enabled with [`AudioManager::set_spectrum_metering`].
+    pub fn spectrum_bands(&self) -> [f32; 3] {
+        let bands = &self.mixer_state.bands;
+        [0, 1, 2].map(|band| bands[band].load(Ordering::Relaxed))
+    }
+
+    /// Smoothed fraction of each block's duration spent in the mixer. Values
+    /// approaching `1` mean the audio thread is close to underrunning.
+    pub fn cpu_load(&self) -> f64 {
+        self.mixer_state.cpu_load.load(Ordering::Relaxed)
+    }
+
+    /// Sets the master volume. This never queues, so it cannot fail with a full buffer.
+    pub fn set_master_volume(&self, volume: f32) {
+        self.mixer_state
+            .master_volume
+            .store(volume, Ordering::Relaxed);
+    }
+
+    pub fn master_volume(&self) -> f32 {
+        self.mixer_state.master_volume.load(Ordering::Relaxed)
+    }
+
+    /// Sets the master tempo that beat-denominated durations, such as
+    /// [`Music::set_loop_region_beats`], are converted against. Defaults to 120.
+    pub fn set_bpm(&self, bpm: f64) {
+        self.mixer_state.bpm.store(bpm, Ordering::Relaxed);
+    }
+
+    pub fn bpm(&self) -> f64 {
+        self.mixer_state.bpm.load(Ordering::Relaxed)
+    }
+
+    /// Fades the whole output to silence over 50 ms when `focused` is false,
+    /// e.g. when another app takes audio focus, and back when it returns.
+    /// Renderers keep running; call [`AudioManager::stop`] after the fade to
+    /// suspend the stream as well.
+    pub fn set_focus(&self, focused: bool) {
+        self.mixer_state.focused.store(focused, Ordering::Relaxed);
+    }
+
+    /// Plays the final mix folded to mono on both channels, for single-ear
+    /// listening or checking mono compatibility. Renderers are unaffected.
+    pub fn set_mono_monitor(&self, enabled: bool) {
+        self.mixer_state.mono_monitor.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Silences the whole output without touching the master volume.
+    pub fn set_all_muted(&self, muted: bool) {
+        self.mixer_state.muted.store(muted, Ordering::Relaxed);
+    }
+
+    /// Whether any output sample has exceeded full scale since the flag was last cleared.
+    pub fn clipping_detected(&self) -> bool {
+        self.mixer_state.clipping.load(Ordering::Relaxed)
+    }
+
+    pub fn clear_clipping_flag(&self) {
+        self.mixer_state.clipping.store(false, Ordering::Relaxed);
+    }
+
     #[inline(always)]
     pub fn consume_broken(&self) -> bool {
         self.backend.consume_broken()
@@ -154,11 +613,37 @@ impl AudioManager {
 
     #[inline(always)]
     pub fn start(&mut self) -> Result<()> {
-        self.backend.start()
+        self.mixer_state.started.store(true, Ordering::Relaxed);
+        self.backend.start()?;
+        self.stopped = false;
+        Ok(())
+    }
+
+    pub fn backend_info(&self) -> BackendInfo {
+        self.backend.info()
+    }
+
+    /// The backend in use, if it is a `B`, for backend-specific runtime settings.
+    pub fn backend_mut<B: Backend>(&mut self) -> Option<&mut B> {
+        (self.backend.as_mut() as &mut dyn Any).downcast_mut()
+    }
+
+    /// Stops the output stream, e.g. while the app is in the background.
+    /// Renderers keep their state and resume on [`AudioManager::start`].
+    pub fn stop(&mut self) -> Result<()> {
+        self.backend.stop()?;
+        self.stopped = true;
+        self.backend.consume_broken();
+        Ok(())
     }
 
+    /// Restarts a stream the backend reported as broken. Does nothing while
+    /// stopped with [`AudioManager::stop`]. Also logs a real-time priority
+    /// failure the audio thread ran into, as it can't log itself.
     pub fn recover_if_needed(&mut self) -> Result<()> {
-        if self.consume_broken() {
+        backend::report_promote_failure();
+        if self.consume_broken() && !self.stopped {
+            log::log(LogLevel::Info, "restarting broken audio stream");
             self.start()
         } else {
             Ok(())
@@ -166,44 +651,157 @@ impl AudioManager {
     }
 }
 
-#[no_mangle]
-pub extern "C" fn create_audio_manager() -> *mut AudioManager {
-    #[cfg(all(not(feature="cpal"), not(feature="oboe")))]
-    return std::ptr::null_mut();
-    #[cfg(feature="cpal")]
+impl Drop for AudioManager {
+    /// Stops the stream before the producer and shared state go away, so no
+    /// callback runs against a half-dropped manager.
+    fn drop(&mut self) {
+        let _ = self.backend.stop();
+    }
+}
+
+#[allow(clippy::vec_init_then_push)]
+fn default_backends() -> Vec<Box<dyn Backend>> {
+    #[allow(unused_mut)]
+    let mut backends: Vec<Box<dyn Backend>> = Vec::new();
+    #[cfg(feature = "oboe")]
     {
-        let settings = backend::cpal::CpalSettings::default();
-        let backend = Box::new(backend::cpal::CpalBackend::new(settings));
-        match AudioManager::new_box(backend){
-            Ok(manager) => Box::into_raw(Box::new(manager)),
-            Err(_) => std::ptr::null_mut(),
-        }
+        backends.push(Box::new(backend::oboe::OboeBackend::new(
+            backend::oboe::OboeSettings {
+                performance_mode: backend::oboe::PerformanceMode::LowLatency,
+                ..Default::default()
+            },
+        )));
+        backends.push(Box::new(backend::oboe::OboeBackend::new(
+            backend::oboe::OboeSettings::default(),
+        )));
     }
-    #[cfg(feature="oboe")]
+    // The default host first, then every other host cpal was built with.
+    #[cfg(feature = "cpal")]
     {
-        let settings = backend::oboe::OboeSettings {
-            performance_mode: backend::oboe::PerformanceMode::LowLatency,
-            ..Default::default()
-        };
-        let backend = Box::new(backend::oboe::OboeBackend::new(settings));
-        match AudioManager::new_box(backend) {
-            Ok(manager) => Box::into_raw(Box::new(manager)),
-            Err(_) => std::ptr::null_mut(),
+        let default_host = ::cpal::default_host().id();
+        let others = ::cpal::available_hosts()
+            .into_iter()
+            .filter(|&id| id != default_host);
+        for host_id in std::iter::once(default_host).chain(others) {
+            backends.push(Box::new(backend::cpal::CpalBackend::new(
+                backend::cpal::CpalSettings {
+                    host_id: Some(host_id),
+                    ..Default::default()
+                },
+            )));
         }
     }
+    #[cfg(all(feature = "default-null-backend", not(any(feature = "cpal", feature = "oboe"))))]
+    backends.push(Box::new(backend::null::NullBackend::new(48000, 2)));
+    backends
+}
+
+thread_local! {
+    /// Message of the last C API call that failed on this thread.
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: &str) {
+    let message = CString::new(message.replace('\0', "")).unwrap();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+/// Keeps the error of a failed C API call for [`get_last_error`].
+fn record<T>(result: Result<T>) -> Option<T> {
+    result.map_err(|err| set_last_error(&format!("{err:#}"))).ok()
+}
+
+/// Describes the last call on this thread that failed with an error, or
+/// returns null if there was none. Successful calls don't clear it, and
+/// null or invalid handles fail without setting it. The string stays valid
+/// until the next failure on this thread.
+#[no_mangle]
+pub extern "C" fn get_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(std::ptr::null(), |message| message.as_ptr())
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn create_audio_manager() -> *mut AudioManager {
+    create_audio_manager_with_retry(INIT_RETRY_TIMEOUT.as_millis() as u64)
+}
+
+/// Like [`create_audio_manager`], but keeps retrying for `timeout_ms`
+/// instead of [`INIT_RETRY_TIMEOUT`] while no backend starts, e.g. while the
+/// output device is still coming up after boot. Zero tries once.
+#[no_mangle]
+pub extern "C" fn create_audio_manager_with_retry(timeout_ms: u64) -> *mut AudioManager {
+    let timeout = Duration::from_millis(timeout_ms);
+    match record(AudioManager::new_with_retry(default_backends, timeout)) {
+        Some(manager) => Box::into_raw(Box::new(manager)),
+        None => std::ptr::null_mut(),
+    }
+}
+
+/// Routes diagnostics such as stream errors and recoveries to `callback`
+/// instead of stderr. Null restores the default.
+#[no_mangle]
+pub extern "C" fn set_log_callback(callback: Option<LogCallback>) {
+    log::set_callback(callback);
 }
 
 #[no_mangle]
 pub extern "C" fn recover_if_needed(manager_ptr: *mut AudioManager) -> bool {
-    if manager_ptr.is_null() {
+    let Some(manager) = (unsafe { checked(manager_ptr) }) else {
         return false;
-    }
-    let manager = unsafe { match manager_ptr.as_mut() {
-            Some(manager) => manager,
-            None => return false,
+    };
+    record(manager.recover_if_needed()).is_some()
+}
+
+#[no_mangle]
+pub extern "C" fn start_manager(manager_ptr: *mut AudioManager) -> bool {
+    let Some(manager) = (unsafe { checked(manager_ptr) }) else {
+        return false;
+    };
+    record(manager.start()).is_some()
+}
+
+#[no_mangle]
+pub extern "C" fn stop_manager(manager_ptr: *mut AudioManager) -> bool {
+    let Some(manager) = (unsafe { checked(manager_ptr) }) else {
+        return false;
+    };
+    record(manager.stop()).is_some()
+}
+
+/// Writes a NUL-terminated description of the backend into `buffer`,
+/// truncating to `len` bytes. Returns the length of the full description.
+#[no_mangle]
+pub extern "C" fn get_backend_info(manager_ptr: *mut AudioManager, buffer: *mut c_char, len: usize) -> usize {
+    let Some(manager) = (unsafe { checked(manager_ptr) }) else {
+        return 0;
+    };
+    let info = manager.backend_info().to_string();
+    if !buffer.is_null() && len > 0 {
+        let copied = info.len().min(len - 1);
+        unsafe {
+            std::ptr::copy_nonoverlapping(info.as_ptr(), buffer as *mut u8, copied);
+            *buffer.add(copied) = 0;
         }
+    }
+    info.len()
+}
+
+#[no_mangle]
+pub extern "C" fn clear_all(manager_ptr: *mut AudioManager) -> bool {
+    let Some(manager) = (unsafe { checked(manager_ptr) }) else {
+        return false;
     };
-    manager.recover_if_needed().is_ok()
+    record(manager.clear_all()).is_some()
+}
+
+/// See [`AudioClip::set_max_decode_frames`].
+#[no_mangle]
+pub extern "C" fn set_max_decode_frames(max: u64) {
+    AudioClip::set_max_decode_frames(max);
 }
 
 #[no_mangle]
@@ -212,14 +810,21 @@ pub extern "C" fn load_audio_clip(path: *const c_char) -> *mut AudioClip {
         return std::ptr::null_mut();
     }
     let path = unsafe { CStr::from_ptr(path) };
-    match std::fs::read(path.to_str().unwrap()) {
-        Ok(data) => {
-            match AudioClip::new(data) {
-                Ok(clip) => Box::into_raw(Box::new(clip)),
-                Err(_) => std::ptr::null_mut(),
-            }
+    let Ok(path) = path.to_str() else {
+        log::log(LogLevel::Error, "load_audio_clip: path is not valid UTF-8");
+        set_last_error("path is not valid UTF-8");
+        return std::ptr::null_mut();
+    };
+    let result = std::fs::read(path)
+        .with_context(|| format!("failed to read {path}"))
+        .and_then(AudioClip::new);
+    match result {
+        Ok(clip) => Box::into_raw(Box::new(clip)),
+        Err(err) => {
+            log::log(LogLevel::Error, &format!("load_audio_clip: {err:#}"));
+            set_last_error(&format!("{err:#}"));
+            std::ptr::null_mut()
         }
-        _ => std::ptr::null_mut(),
     }
 }
 
@@ -229,124 +834,185 @@ pub extern "C" fn load_audio_clip_from_buffer(data: *const u8, size: usize) -> *
         return std::ptr::null_mut();
     }
     let data = unsafe { slice::from_raw_parts(data, size) };
-    match AudioClip::new(data.to_vec()) {
-        Ok(clip) => Box::into_raw(Box::new(clip)), 
-        Err(_) => std::ptr::null_mut(),
+    match record(AudioClip::new(data.to_vec())) {
+        Some(clip) => Box::into_raw(Box::new(clip)), 
+        None => std::ptr::null_mut(),
     }
 }
 
 #[no_mangle]
 pub extern "C" fn create_sfx(manager_ptr: *mut AudioManager, clip_ptr: *mut AudioClip) -> *mut Sfx {
-    if manager_ptr.is_null() || clip_ptr.is_null() {
+    let manager = unsafe { checked(manager_ptr) };
+    let clip = unsafe { checked(clip_ptr) };
+    let (Some(manager), Some(clip)) = (manager, clip) else {
         return std::ptr::null_mut();
-    }
-    let manager = unsafe { manager_ptr.as_mut().unwrap() };
-    let clip = unsafe { &*clip_ptr };
-    match manager.create_sfx(clip.clone(), Some(1024)) {
-        Ok(sfx) => {
+    };
+    match record(manager.create_sfx(clip.clone(), Some(1024))) {
+        Some(sfx) => {
             Box::into_raw(Box::new(sfx))
         },
-        Err(_) => std::ptr::null_mut(),
+        None => std::ptr::null_mut(),
     }
 }
 
 #[no_mangle]
 pub extern "C" fn create_music(manager_ptr: *mut AudioManager, clip_ptr: *mut AudioClip, playback_rate: f64) -> *mut Music {
-    if manager_ptr.is_null() || clip_ptr.is_null() {
+    let manager = unsafe { checked(manager_ptr) };
+    let clip = unsafe { checked(clip_ptr) };
+    let (Some(manager), Some(clip)) = (manager, clip) else {
         return std::ptr::null_mut();
-    }
-    let manager = unsafe { manager_ptr.as_mut().unwrap() };
-    let clip = unsafe { &*clip_ptr };
+    };
     let params = MusicParams {
         playback_rate,
         ..Default::default()
     };
-    match manager.create_music(clip.clone(), params) {
-        Ok(music) => {
+    match record(manager.create_music(clip.clone(), params)) {
+        Some(music) => {
             Box::into_raw(Box::new(music))
         },
-        Err(_) => std::ptr::null_mut(),
+        None => std::ptr::null_mut(),
     }
 }
 
 #[no_mangle]
 pub extern "C" fn play_sfx(sfx_ptr: *mut Sfx, volume: f32) -> bool {
-    if sfx_ptr.is_null() {
+    let Some(sfx) = (unsafe { checked(sfx_ptr) }) else {
         return false;
-    }
-    let sfx = unsafe { sfx_ptr.as_mut().unwrap() };
-    sfx.play(PlaySfxParams { amplifier: volume }).is_ok()
+    };
+    sfx.play(PlaySfxParams {
+        amplifier: volume,
+        ..Default::default()
+    })
+    .voice()
+    .is_some()
+}
+
+#[no_mangle]
+pub extern "C" fn play_clip_oneshot(manager_ptr: *mut AudioManager, clip_ptr: *mut AudioClip, volume: f32) -> bool {
+    let manager = unsafe { checked(manager_ptr) };
+    let clip = unsafe { checked(clip_ptr) };
+    let (Some(manager), Some(clip)) = (manager, clip) else {
+        return false;
+    };
+    let result = manager.play_oneshot(
+        clip.clone(),
+        PlaySfxParams {
+            amplifier: volume,
+            ..Default::default()
+        },
+    );
+    record(result).is_some()
 }
 
 #[no_mangle]
 pub extern "C" fn play_music(music_ptr: *mut Music, volume: f32) -> bool {
-    if music_ptr.is_null() {
+    let Some(music) = (unsafe { checked(music_ptr) }) else {
         return false;
-    }
-    let music = unsafe { music_ptr.as_mut().unwrap() };
-    match music.set_amplifier(volume) {
-        Ok(_) => music.play().is_ok(),
-        Err(_) => false,
-    }
+    };
+    record(music.set_amplifier(volume).and_then(|_| music.play())).is_some()
 }
 
 #[no_mangle]
 pub extern "C" fn pause_music(music_ptr: *mut Music) -> bool {
-    if music_ptr.is_null() {
+    let Some(music) = (unsafe { checked(music_ptr) }) else {
         return false;
-    }
-    let music = unsafe { music_ptr.as_mut().unwrap() };
-    music.pause().is_ok()
+    };
+    record(music.pause()).is_some()
+}
+
+#[no_mangle]
+pub extern "C" fn stop_music(music_ptr: *mut Music) -> bool {
+    let Some(music) = (unsafe { checked(music_ptr) }) else {
+        return false;
+    };
+    record(music.stop()).is_some()
 }
 
 #[no_mangle]
 pub extern "C" fn is_music_paused(music_ptr: *mut Music) -> bool {
-    if music_ptr.is_null() {
+    let Some(music) = (unsafe { checked(music_ptr) }) else {
         return true;
-    }
-    let music = unsafe { music_ptr.as_mut().unwrap() };
+    };
     music.paused()
 }
 
 #[no_mangle]
 pub extern "C" fn seek_music(music_ptr: *mut Music, time: f64) -> bool {
-    if music_ptr.is_null() {
+    let Some(music) = (unsafe { checked(music_ptr) }) else {
         return false;
-    }
-    let music = unsafe { music_ptr.as_mut().unwrap() };
-    music.seek_to(time).is_ok()
+    };
+    record(music.seek_to(time)).is_some()
+}
+
+#[no_mangle]
+pub extern "C" fn seek_music_fraction(music_ptr: *mut Music, fraction: f64) -> bool {
+    let Some(music) = (unsafe { checked(music_ptr) }) else {
+        return false;
+    };
+    record(music.seek_fraction(fraction)).is_some()
 }
 
 #[no_mangle]
 pub extern "C" fn set_music_volume(music_ptr: *mut Music, volume: f32) -> bool {
-    if music_ptr.is_null() {
+    let Some(music) = (unsafe { checked(music_ptr) }) else {
         return false;
-    }
-    let music = unsafe { music_ptr.as_mut().unwrap() };
-    music.set_amplifier(volume).is_ok()
+    };
+    record(music.set_amplifier(volume)).is_some()
+}
+
+#[no_mangle]
+pub extern "C" fn set_music_volume_immediate(music_ptr: *mut Music, volume: f32) -> bool {
+    let Some(music) = (unsafe { checked(music_ptr) }) else {
+        return false;
+    };
+    record(music.set_amplifier_immediate(volume)).is_some()
+}
+
+#[no_mangle]
+pub extern "C" fn set_music_muted(music_ptr: *mut Music, muted: bool) -> bool {
+    let Some(music) = (unsafe { checked(music_ptr) }) else {
+        return false;
+    };
+    record(music.set_muted(muted)).is_some()
 }
 
 #[no_mangle]
 pub extern "C" fn get_music_position(music_ptr: *mut Music) -> f64 {
-    if music_ptr.is_null() {
+    let Some(music) = (unsafe { checked(music_ptr) }) else {
         return 0.0;
-    }
-    let music = unsafe { music_ptr.as_mut().unwrap() };
+    };
     music.position()
 }
 
+#[no_mangle]
+pub extern "C" fn save_audio_clip(clip_ptr: *mut AudioClip, path: *const c_char) -> bool {
+    if path.is_null() {
+        return false;
+    }
+    let Some(clip) = (unsafe { checked(clip_ptr) }) else {
+        return false;
+    };
+    let path = unsafe { CStr::from_ptr(path) };
+    match path.to_str() {
+        Ok(path) => record(clip.write_wav(path)).is_some(),
+        Err(_) => {
+            set_last_error("path is not valid UTF-8");
+            false
+        }
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn get_audio_clip_duration(clip_ptr: *mut AudioClip) -> f64 {
-    if clip_ptr.is_null() {
+    let Some(clip) = (unsafe { checked(clip_ptr) }) else {
         return 0.0;
-    }
-    let clip = unsafe { &*clip_ptr };
+    };
     clip.length()
 }
 
 #[no_mangle]
 pub extern "C" fn destroy_manager(manager_ptr: *mut AudioManager) {
-    if !manager_ptr.is_null() {
+    if unsafe { checked(manager_ptr) }.is_some() {
         unsafe {
             let _ = Box::from_raw(manager_ptr);
         };
@@ -355,7 +1021,7 @@ pub extern "C" fn destroy_manager(manager_ptr: *mut AudioManager) {
 
 #[no_mangle]
 pub extern "C" fn destroy_clip(clip_ptr: *mut AudioClip) {
-    if !clip_ptr.is_null() {
+    if unsafe { checked(clip_ptr) }.is_some() {
         unsafe {
             let _ = Box::from_raw(clip_ptr);
         };
@@ -364,7 +1030,7 @@ pub extern "C" fn destroy_clip(clip_ptr: *mut AudioClip) {
 
 #[no_mangle]
 pub extern "C" fn destroy_sfx(sfx_ptr: *mut Sfx) {
-    if !sfx_ptr.is_null() {
+    if unsafe { checked(sfx_ptr) }.is_some() {
         unsafe {
             let _ = Box::from_raw(sfx_ptr);
         };
@@ -373,9 +1039,64 @@ pub extern "C" fn destroy_sfx(sfx_ptr: *mut Sfx) {
 
 #[no_mangle]
 pub extern "C" fn destroy_music(music_ptr: *mut Music) {
-    if !music_ptr.is_null() {
+    if unsafe { checked(music_ptr) }.is_some() {
         unsafe {
             let _ = Box::from_raw(music_ptr);
         };
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{manual::ManualBackend, null::NullBackend};
+    use std::{sync::atomic::AtomicUsize, thread};
+
+    /// Counts its render calls.
+    struct Counter(Arc<AtomicUsize>);
+
+    impl Renderer for Counter {
+        fn alive(&self) -> bool {
+            true
+        }
+
+        fn render_mono(&mut self, _: u32, _: &mut [f32]) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+
+        fn render_stereo(&mut self, _: u32, _: &mut [f32]) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn add_counter(manager: &mut AudioManager) -> Arc<AtomicUsize> {
+        let count = Arc::default();
+        manager.add_renderer(Counter(Arc::clone(&count))).unwrap();
+        count
+    }
+
+    #[test]
+    fn manual_backend_stops_rendering_on_drop() {
+        let backend = ManualBackend::new(48000, 2);
+        let mut manager = AudioManager::new(backend.clone()).unwrap();
+        let count = add_counter(&mut manager);
+        backend.render(64);
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+        drop(manager);
+        backend.render(64);
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn null_backend_stops_rendering_on_drop() {
+        let mut manager = AudioManager::new(NullBackend::new(48000, 2)).unwrap();
+        let count = add_counter(&mut manager);
+        while count.load(Ordering::SeqCst) == 0 {
+            thread::sleep(Duration::from_millis(1));
+        }
+        drop(manager);
+        let after_drop = count.load(Ordering::SeqCst);
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(count.load(Ordering::SeqCst), after_drop);
+    }
 }
\ No newline at end of file
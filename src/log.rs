@@ -0,0 +1,34 @@
+use std::{
+    ffi::{c_char, CString},
+    sync::Mutex,
+};
+
+/// Severity passed to the log callback.
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogLevel {
+    Info = 0,
+    Warn = 1,
+    Error = 2,
+}
+
+pub type LogCallback = extern "C" fn(level: i32, msg: *const c_char);
+
+static CALLBACK: Mutex<Option<LogCallback>> = Mutex::new(None);
+
+pub(crate) fn set_callback(callback: Option<LogCallback>) {
+    *CALLBACK.lock().unwrap() = callback;
+}
+
+/// Reports an event that doesn't surface through a return value, such as a
+/// stream error on the audio thread. Never call this from the render path.
+pub(crate) fn log(level: LogLevel, msg: &str) {
+    let callback = *CALLBACK.lock().unwrap();
+    match callback {
+        Some(callback) => {
+            let msg = CString::new(msg.replace('\0', "")).unwrap();
+            callback(level as i32, msg.as_ptr());
+        }
+        None => eprintln!("{msg}"),
+    }
+}
@@ -0,0 +1,252 @@
+use crate::mixer::MixerState;
+use std::sync::atomic::Ordering;
+
+const MAX_CHANNELS: usize = 8;
+/// Gating blocks are 400 ms long and advance in 100 ms steps.
+const MOMENTARY_STEPS: usize = 4;
+const SHORT_TERM_STEPS: usize = 30;
+const ABSOLUTE_GATE: f64 = -70.;
+const RELATIVE_GATE: f64 = -10.;
+/// Integrated loudness is accumulated in 0.1 LU bins from the absolute gate
+/// up to +5 LUFS, so memory stays fixed however long the stream runs.
+const HISTOGRAM_STEP: f64 = 0.1;
+const HISTOGRAM_BINS: usize = 750;
+/// BS.1770 weight of the surround channels, +1.5 dB.
+const SURROUND_WEIGHT: f64 = 1.41;
+
+/// Master loudness per ITU-R BS.1770, in LUFS. Values are negative infinity
+/// until enough audio has been measured.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoudnessStats {
+    /// Over the last 400 ms.
+    pub momentary: f32,
+    /// Over the last 3 s.
+    pub short_term: f32,
+    /// Gated average since metering was enabled.
+    pub integrated: f32,
+}
+
+#[inline]
+fn loudness(energy: f64) -> f64 {
+    if energy > 0. {
+        -0.691 + 10. * energy.log10()
+    } else {
+        f64::NEG_INFINITY
+    }
+}
+
+/// BS.1770 channel weights, assuming the WAVE channel order: L, R, C, LFE,
+/// then surrounds for 5.1 and up; L, R, (C,) Ls, Rs for four and five
+/// channels. The LFE is excluded.
+fn channel_weights(channels: usize) -> [f64; MAX_CHANNELS] {
+    let mut weights = [1.; MAX_CHANNELS];
+    match channels {
+        4 | 5 => weights[channels - 2..channels].fill(SURROUND_WEIGHT),
+        6.. => {
+            weights[3] = 0.;
+            weights[4..].fill(SURROUND_WEIGHT);
+        }
+        _ => {}
+    }
+    weights
+}
+
+#[derive(Clone, Copy, Default)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+}
+
+impl Biquad {
+    /// Stage one of the K-weighting filter, modelling the head's acoustic effect.
+    fn shelf(sample_rate: u32) -> Self {
+        let f0 = 1681.974450955533;
+        let gain = 3.999843853973347;
+        let q = 0.7071752369554196;
+        let k = (std::f64::consts::PI * f0 / sample_rate as f64).tan();
+        let vh = 10f64.powf(gain / 20.);
+        let vb = vh.powf(0.4996667741545416);
+        let a0 = 1. + k / q + k * k;
+        Self {
+            b: [
+                (vh + vb * k / q + k * k) / a0,
+                2. * (k * k - vh) / a0,
+                (vh - vb * k / q + k * k) / a0,
+            ],
+            a: [2. * (k * k - 1.) / a0, (1. - k / q + k * k) / a0],
+        }
+    }
+
+    /// Stage two of the K-weighting filter, the RLB high-pass.
+    fn high_pass(sample_rate: u32) -> Self {
+        let f0 = 38.13547087602444;
+        let q = 0.5003270373238773;
+        let k = (std::f64::consts::PI * f0 / sample_rate as f64).tan();
+        let a0 = 1. + k / q + k * k;
+        Self {
+            b: [1., -2., 1.],
+            a: [2. * (k * k - 1.) / a0, (1. - k / q + k * k) / a0],
+        }
+    }
+
+    #[inline]
+    fn process(&self, z: &mut [f64; 2], x: f64) -> f64 {
+        let y = self.b[0] * x + z[0];
+        z[0] = self.b[1] * x - self.a[0] * y + z[1];
+        z[1] = self.b[2] * x - self.a[1] * y;
+        y
+    }
+}
+
+/// K-weighted loudness meter run on the mixer output. Built on the control
+/// thread so the audio thread never allocates its histogram.
+pub(crate) struct LoudnessMeter {
+    sample_rate: u32,
+    shelf: Biquad,
+    high_pass: Biquad,
+    /// Filter state per channel, for each of the two stages.
+    state: [[[f64; 2]; 2]; MAX_CHANNELS],
+    step_frames: usize,
+    step_pos: usize,
+    step_sum: f64,
+    /// Mean square of the most recent 100 ms steps, as a ring.
+    steps: [f64; SHORT_TERM_STEPS],
+    step_count: usize,
+    /// Count and summed energy of gating blocks per loudness bin.
+    histogram: Box<[(u64, f64)]>,
+}
+
+impl LoudnessMeter {
+    pub(crate) fn new(sample_rate: u32) -> Self {
+        let mut meter = Self {
+            sample_rate: 0,
+            shelf: Biquad::default(),
+            high_pass: Biquad::default(),
+            state: Default::default(),
+            step_frames: 0,
+            step_pos: 0,
+            step_sum: 0.,
+            steps: [0.; SHORT_TERM_STEPS],
+            step_count: 0,
+            histogram: vec![(0, 0.); HISTOGRAM_BINS].into_boxed_slice(),
+        };
+        meter.set_sample_rate(sample_rate);
+        meter
+    }
+
+    /// Recomputes the filters for a new rate and restarts the measurement.
+    pub(crate) fn set_sample_rate(&mut self, sample_rate: u32) {
+        if self.sample_rate == sample_rate {
+            return;
+        }
+        self.sample_rate = sample_rate;
+        self.shelf = Biquad::shelf(sample_rate);
+        self.high_pass = Biquad::high_pass(sample_rate);
+        self.state = Default::default();
+        self.step_frames = (sample_rate as usize / 10).max(1);
+        self.step_pos = 0;
+        self.step_sum = 0.;
+        self.step_count = 0;
+        self.histogram.fill((0, 0.));
+    }
+
+    pub(crate) fn process(&mut self, data: &[f32], channels: usize, state: &MixerState) {
+        let weights = channel_weights(channels);
+        for frame in data.chunks_exact(channels) {
+            for ((sample, z), weight) in frame.iter().zip(&mut self.state).zip(weights) {
+                let x = self.shelf.process(&mut z[0], *sample as f64);
+                let y = self.high_pass.process(&mut z[1], x);
+                self.step_sum += weight * y * y;
+            }
+            self.step_pos += 1;
+            if self.step_pos == self.step_frames {
+                self.finish_step(state);
+            }
+        }
+    }
+
+    fn finish_step(&mut self, state: &MixerState) {
+        self.steps[self.step_count % SHORT_TERM_STEPS] = self.step_sum / self.step_frames as f64;
+        self.step_count += 1;
+        self.step_pos = 0;
+        self.step_sum = 0.;
+
+        let momentary = self.mean_of_last(MOMENTARY_STEPS);
+        if let Some(energy) = momentary {
+            let level = loudness(energy);
+            if level >= ABSOLUTE_GATE {
+                let bin = ((level - ABSOLUTE_GATE) / HISTOGRAM_STEP) as usize;
+                let bin = &mut self.histogram[bin.min(HISTOGRAM_BINS - 1)];
+                bin.0 += 1;
+                bin.1 += energy;
+            }
+        }
+        let short_term = self.mean_of_last(SHORT_TERM_STEPS);
+        let store = |energy: Option<f64>| energy.map_or(f32::NEG_INFINITY, |e| loudness(e) as f32);
+        state
+            .momentary_loudness
+            .store(store(momentary), Ordering::Relaxed);
+        state
+            .short_term_loudness
+            .store(store(short_term), Ordering::Relaxed);
+        state
+            .integrated_loudness
+            .store(self.integrated() as f32, Ordering::Relaxed);
+    }
+
+    fn mean_of_last(&self, steps: usize) -> Option<f64> {
+        if self.step_count < steps {
+            return None;
+        }
+        let sum: f64 = (self.step_count - steps..self.step_count)
+            .map(|step| self.steps[step % SHORT_TERM_STEPS])
+            .sum();
+        Some(sum / steps as f64)
+    }
+
+    fn integrated(&self) -> f64 {
+        let gated_mean = |first_bin: usize| {
+            let (count, energy) = self.histogram[first_bin..]
+                .iter()
+                .fold((0, 0.), |(count, energy), bin| {
+                    (count + bin.0, energy + bin.1)
+                });
+            (count > 0).then(|| energy / count as f64)
+        };
+        let Some(ungated) = gated_mean(0) else {
+            return f64::NEG_INFINITY;
+        };
+        let threshold = loudness(ungated) + RELATIVE_GATE;
+        let first_bin = ((threshold - ABSOLUTE_GATE) / HISTOGRAM_STEP)
+            .max(0.)
+            .ceil() as usize;
+        gated_mean(first_bin.min(HISTOGRAM_BINS)).map_or(f64::NEG_INFINITY, loudness)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Momentary loudness of a 1 kHz tone at full scale on `channel` alone.
+    fn tone_loudness(channels: usize, channel: usize) -> f32 {
+        let mut meter = LoudnessMeter::new(48000);
+        let state = MixerState::default();
+        let mut data = vec![0.; 48000 * channels];
+        for (index, frame) in data.chunks_exact_mut(channels).enumerate() {
+            let phase = 2. * std::f32::consts::PI * 1000. * index as f32 / 48000.;
+            frame[channel] = phase.sin();
+        }
+        meter.process(&data, channels, &state);
+        state.momentary_loudness.load(Ordering::Relaxed)
+    }
+
+    #[test]
+    fn weights_surround_channels() {
+        let front = tone_loudness(6, 0);
+        assert!((front - tone_loudness(2, 0)).abs() < 0.01);
+        assert!((tone_loudness(6, 2) - front).abs() < 0.01);
+        assert!((tone_loudness(6, 4) - front - 1.49).abs() < 0.01);
+        assert_eq!(tone_loudness(6, 3), f32::NEG_INFINITY);
+    }
+}
@@ -1,51 +1,767 @@
 use ringbuf::HeapConsumer;
-use crate::Renderer;
+use crate::{
+    bus::{BusEffect, BusId},
+    loudness::LoudnessMeter,
+    ramp::{seconds_to_frames, GainRamp},
+    Frame, Renderer, RendererId,
+};
+use atomic_float::{AtomicF32, AtomicF64};
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+
+/// Time over which the whole output fades on audio focus changes.
+const FOCUS_FADE_TIME: f64 = 0.05;
+
+/// How renderer outputs are combined.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MixMode {
+    /// Plain sum of all renderers.
+    #[default]
+    Sum,
+    /// Sum scaled by `1 / n` for `n` active voices.
+    NormalizedByCount,
+    /// Sum scaled by `1 / sqrt(n)` for `n` active voices.
+    ConstantPower,
+}
 
 pub(crate) enum MixerCommand {
-    AddRenderer(Box<dyn Renderer>),
+    /// A renderer and whether it starts out bypassing the master.
+    AddRenderer(RendererId, Box<dyn Renderer>, bool),
+    SetMixMode(MixMode),
+    Solo(Option<RendererId>),
+    SetOutputChannels(RendererId, (usize, usize)),
+    SetBypassMaster(RendererId, bool),
+    SetMaxActive(usize),
+    SetDcBlock(bool),
+    ClearAll,
+    AutomateMasterVolume(Vec<(f64, f32)>),
+    SetLoudnessMeter(Option<Box<LoudnessMeter>>),
+    AddBus(BusId, Box<dyn BusEffect>),
+    RemoveBus(BusId),
+    SetSend(RendererId, BusId, f32),
+}
+
+impl fmt::Debug for MixerCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AddRenderer(id, _, bypass) => write!(f, "AddRenderer({id:?}, {bypass})"),
+            Self::SetMixMode(mode) => write!(f, "SetMixMode({mode:?})"),
+            Self::Solo(id) => write!(f, "Solo({id:?})"),
+            Self::SetOutputChannels(id, channels) => {
+                write!(f, "SetOutputChannels({id:?}, {channels:?})")
+            }
+            Self::SetBypassMaster(id, bypass) => write!(f, "SetBypassMaster({id:?}, {bypass})"),
+            Self::SetMaxActive(max) => write!(f, "SetMaxActive({max})"),
+            Self::SetDcBlock(enabled) => write!(f, "SetDcBlock({enabled})"),
+            Self::ClearAll => write!(f, "ClearAll"),
+            Self::AutomateMasterVolume(points) => write!(f, "AutomateMasterVolume({points:?})"),
+            Self::SetLoudnessMeter(meter) => write!(f, "SetLoudnessMeter({})", meter.is_some()),
+            Self::AddBus(id, _) => write!(f, "AddBus({id:?})"),
+            Self::RemoveBus(id) => write!(f, "RemoveBus({id:?})"),
+            Self::SetSend(id, bus, level) => write!(f, "SetSend({id:?}, {bus:?}, {level})"),
+        }
+    }
+}
+
+struct RendererSlot {
+    id: RendererId,
+    renderer: Box<dyn Renderer>,
+    /// Left/right output channels used when the backend has more than two.
+    output_channels: (usize, usize),
+    /// Mixed in after master volume, mute and the other master gains.
+    bypass_master: bool,
+    /// Levels at which the output is also sent to effect buses.
+    sends: Vec<(BusId, f32)>,
+    /// RMS of the output over the last block, measured while `max_active`
+    /// is set.
+    level: f32,
+    /// [`Renderer::stop`] left it sounding, so `max_active` can't stop it.
+    unstoppable: bool,
+}
+
+struct Bus {
+    id: BusId,
+    effect: Box<dyn BusEffect>,
+    /// Sends summed over the current block.
+    buffer: Vec<f32>,
+}
+
+/// Master gain breakpoints, followed against the mixer's sample clock.
+struct MasterAutomation {
+    /// Sorted `(seconds, gain)` pairs, relative to when they were received.
+    points: Vec<(f64, f32)>,
+    /// Gain when the breakpoints were received, ramped from towards the first.
+    start_gain: f32,
+    /// Index of the first breakpoint not yet reached.
+    next: usize,
+    elapsed_frames: u64,
+    current: f32,
+}
+
+impl MasterAutomation {
+    fn set_points(&mut self, points: Vec<(f64, f32)>) {
+        self.points = points;
+        self.start_gain = self.current;
+        self.next = 0;
+        self.elapsed_frames = 0;
+    }
+
+    #[inline(always)]
+    fn is_idle_at_unity(&self) -> bool {
+        self.next == self.points.len() && self.current == 1.
+    }
+
+    #[inline]
+    fn next(&mut self, sample_rate: u32) -> f32 {
+        if self.next == self.points.len() {
+            return self.current;
+        }
+        let time = self.elapsed_frames as f64 / sample_rate as f64;
+        self.elapsed_frames += 1;
+        while self.points.get(self.next).is_some_and(|(at, _)| *at <= time) {
+            self.current = self.points[self.next].1;
+            self.next += 1;
+        }
+        if let Some(&(end, end_gain)) = self.points.get(self.next) {
+            let (start, start_gain) = match self.next {
+                0 => (0., self.start_gain),
+                next => self.points[next - 1],
+            };
+            let mix = ((time - start) / (end - start)) as f32;
+            self.current = start_gain + (end_gain - start_gain) * mix;
+        }
+        self.current
+    }
+}
+
+/// Latest-wins state shared with the control thread. Unlike commands these never queue up.
+pub(crate) struct MixerState {
+    pub(crate) master_volume: AtomicF32,
+    pub(crate) muted: AtomicBool,
+    /// Cleared while another app holds audio focus.
+    pub(crate) focused: AtomicBool,
+    /// Folds the final mix to mono on both channels.
+    pub(crate) mono_monitor: AtomicBool,
+    pub(crate) clipping: AtomicBool,
+    /// Bumped for every command the mixer has dequeued.
+    pub(crate) processed_commands: AtomicU64,
+    /// Set by the control thread whenever the stream (re)starts.
+    pub(crate) started: AtomicBool,
+    pub(crate) startup_fade: f64,
+    /// Frames per internal processing block, `0` for the backend's size.
+    pub(crate) block_size: usize,
+    /// Output sample rate negotiated by the backend, `0` until known.
+    pub(crate) sample_rate: AtomicU32,
+    /// Renderers with at least one sounding voice in the last block.
+    pub(crate) active_renderers: AtomicUsize,
+    /// Frames requested by the backend in the last callback.
+    pub(crate) block_frames: AtomicUsize,
+    /// Smoothed time spent rendering a block relative to its duration.
+    pub(crate) cpu_load: AtomicF64,
+    /// See [`AudioManager::set_spectrum_metering`](crate::AudioManager::set_spectrum_metering);
+    /// band analysis is skipped while unset.
+    pub(crate) band_metering: AtomicBool,
+    /// RMS of the low, mid and high bands of the output over the last block.
+    pub(crate) bands: [AtomicF32; 3],
+    /// Master tempo in beats per minute.
+    pub(crate) bpm: AtomicF64,
+    pub(crate) momentary_loudness: AtomicF32,
+    pub(crate) short_term_loudness: AtomicF32,
+    pub(crate) integrated_loudness: AtomicF32,
+}
+impl Default for MixerState {
+    fn default() -> Self {
+        Self {
+            master_volume: AtomicF32::new(1.),
+            muted: AtomicBool::new(false),
+            focused: AtomicBool::new(true),
+            mono_monitor: AtomicBool::new(false),
+            clipping: AtomicBool::new(false),
+            processed_commands: AtomicU64::new(0),
+            started: AtomicBool::new(false),
+            startup_fade: 0.,
+            block_size: 0,
+            sample_rate: AtomicU32::new(0),
+            active_renderers: AtomicUsize::new(0),
+            block_frames: AtomicUsize::new(0),
+            cpu_load: AtomicF64::new(0.),
+            band_metering: AtomicBool::new(false),
+            bands: Default::default(),
+            bpm: AtomicF64::new(120.),
+            momentary_loudness: AtomicF32::new(f32::NEG_INFINITY),
+            short_term_loudness: AtomicF32::new(f32::NEG_INFINITY),
+            integrated_loudness: AtomicF32::new(f32::NEG_INFINITY),
+        }
+    }
 }
+
 pub(crate) struct Mixer {
-    pub(crate) sample_rate: u32,
+    sample_rate: u32,
 
-    renderers: Vec<Box<dyn Renderer>>,
+    renderers: Vec<RendererSlot>,
     cons: HeapConsumer<MixerCommand>,
+    state: Arc<MixerState>,
+    mute: GainRamp,
+    mix_mode: MixMode,
+    mix_gain: GainRamp,
+    startup: GainRamp,
+    solo: Option<RendererId>,
+    /// Output of renderers muted by solo, rendered only to keep them advancing,
+    /// or stereo output waiting to be routed into a multichannel buffer.
+    scratch: Vec<f32>,
+    /// Output of renderers that bypass the master gains.
+    bypass: Vec<f32>,
+    /// Output of a single renderer, kept apart to split it to its buses or
+    /// to measure its level.
+    isolated: Vec<f32>,
+    buses: Vec<Bus>,
+    max_active: usize,
+    /// Rendered samples of the last fixed-size block not yet handed out.
+    pending: Vec<f32>,
+    pending_start: usize,
+    dc_block: bool,
+    /// Previous input and output of the DC blocker, per channel.
+    dc_state: Vec<(f32, f32)>,
+    /// Low-pass states of the band analysis crossovers.
+    band_state: (f32, f32),
+    /// Newest renderer to remove, with all older ones, once `clear_fade`
+    /// reaches silence.
+    clearing: Option<RendererId>,
+    clear_fade: GainRamp,
+    loudness: Option<Box<LoudnessMeter>>,
+    /// Follows `MixerState::focused`, applied after the bypass mix.
+    focus: GainRamp,
+    automation: MasterAutomation,
+    #[cfg(feature = "trace-commands")]
+    epoch: Instant,
 }
 
 impl Mixer {
-    pub(crate) fn new(sample_rate: u32, cons: HeapConsumer<MixerCommand>) -> Self {
+    pub(crate) fn new(
+        sample_rate: u32,
+        cons: HeapConsumer<MixerCommand>,
+        state: Arc<MixerState>,
+    ) -> Self {
+        state.sample_rate.store(sample_rate, Ordering::Relaxed);
         Self {
             sample_rate,
 
             renderers: Vec::new(),
             cons,
+            state,
+            mute: GainRamp::new(1.),
+            mix_mode: MixMode::Sum,
+            mix_gain: GainRamp::new(1.),
+            startup: GainRamp::new(1.),
+            solo: None,
+            scratch: Vec::new(),
+            bypass: Vec::new(),
+            isolated: Vec::new(),
+            buses: Vec::new(),
+            max_active: usize::MAX,
+            pending: Vec::new(),
+            pending_start: 0,
+            dc_block: false,
+            dc_state: Vec::new(),
+            band_state: (0., 0.),
+            clearing: None,
+            clear_fade: GainRamp::new(1.),
+            loudness: None,
+            focus: GainRamp::new(1.),
+            automation: MasterAutomation {
+                points: Vec::new(),
+                start_gain: 1.,
+                next: 0,
+                elapsed_frames: 0,
+                current: 1.,
+            },
+            #[cfg(feature = "trace-commands")]
+            epoch: Instant::now(),
+        }
+    }
+
+    pub(crate) fn set_sample_rate(&mut self, sample_rate: u32) {
+        if self.sample_rate != sample_rate {
+            self.sample_rate = sample_rate;
+            self.state.sample_rate.store(sample_rate, Ordering::Relaxed);
+            if let Some(meter) = &mut self.loudness {
+                meter.set_sample_rate(sample_rate);
+            }
         }
     }
 
     fn consume_commands(&mut self) {
         for cmd in self.cons.pop_iter() {
+            #[cfg(feature = "trace-commands")]
+            let trace = format!("{cmd:?}");
             match cmd {
-                MixerCommand::AddRenderer(renderer) => self.renderers.push(renderer),
+                MixerCommand::AddRenderer(id, renderer, bypass_master) => {
+                    self.renderers.push(RendererSlot {
+                        id,
+                        renderer,
+                        output_channels: (0, 1),
+                        bypass_master,
+                        sends: Vec::new(),
+                        level: 0.,
+                        unstoppable: false,
+                    })
+                }
+                MixerCommand::SetMixMode(mode) => self.mix_mode = mode,
+                MixerCommand::Solo(id) => self.solo = id,
+                MixerCommand::SetMaxActive(max) => self.max_active = max,
+                MixerCommand::SetLoudnessMeter(mut meter) => {
+                    if let Some(meter) = &mut meter {
+                        meter.set_sample_rate(self.sample_rate);
+                    }
+                    self.loudness = meter;
+                    for loudness in [
+                        &self.state.momentary_loudness,
+                        &self.state.short_term_loudness,
+                        &self.state.integrated_loudness,
+                    ] {
+                        loudness.store(f32::NEG_INFINITY, Ordering::Relaxed);
+                    }
+                }
+                MixerCommand::ClearAll => {
+                    if let Some(last) = self.renderers.last() {
+                        self.clearing = Some(last.id);
+                        self.clear_fade.declick_to(0., self.sample_rate);
+                    }
+                }
+                MixerCommand::AutomateMasterVolume(points) => {
+                    self.automation.set_points(points);
+                }
+                MixerCommand::SetDcBlock(enabled) => {
+                    self.dc_block = enabled;
+                    self.dc_state.clear();
+                }
+                MixerCommand::SetBypassMaster(id, bypass) => {
+                    if let Some(slot) = self.renderers.iter_mut().find(|slot| slot.id == id) {
+                        slot.bypass_master = bypass;
+                    }
+                }
+                MixerCommand::AddBus(id, effect) => self.buses.push(Bus {
+                    id,
+                    effect,
+                    buffer: Vec::new(),
+                }),
+                MixerCommand::RemoveBus(id) => self.buses.retain(|bus| bus.id != id),
+                MixerCommand::SetSend(id, bus, level) => {
+                    if let Some(slot) = self.renderers.iter_mut().find(|slot| slot.id == id) {
+                        slot.sends.retain(|(send, _)| *send != bus);
+                        if level != 0. {
+                            slot.sends.push((bus, level));
+                        }
+                    }
+                }
+                MixerCommand::SetOutputChannels(id, channels) => {
+                    if let Some(slot) = self.renderers.iter_mut().find(|slot| slot.id == id) {
+                        slot.output_channels = channels;
+                    }
+                }
             }
+            let _seq = self.state.processed_commands.fetch_add(1, Ordering::Release);
+            #[cfg(feature = "trace-commands")]
+            crate::log::log(
+                crate::log::LogLevel::Info,
+                &format!(
+                    "mixer command #{_seq} {trace} processed at {:.6}s",
+                    self.epoch.elapsed().as_secs_f64()
+                ),
+            );
         }
     }
 
-    pub fn render_mono(&mut self, data: &mut [f32]) {
+    fn update_mix_gain(&mut self) {
+        let voices = self
+            .renderers
+            .iter()
+            .map(|slot| slot.renderer.active_voices())
+            .sum::<usize>()
+            .max(1) as f32;
+        let target = match self.mix_mode {
+            MixMode::Sum => 1.,
+            MixMode::NormalizedByCount => 1. / voices,
+            MixMode::ConstantPower => 1. / voices.sqrt(),
+        };
+        if self.mix_gain.target() != target {
+            self.mix_gain.declick_to(target, self.sample_rate);
+        }
+    }
+
+    fn post_process(&mut self, data: &mut [f32], channels: usize) {
+        self.update_mix_gain();
+        if self.state.started.swap(false, Ordering::Relaxed) && self.state.startup_fade > 0. {
+            self.startup = GainRamp::new(0.);
+            self.startup
+                .set_target(1., seconds_to_frames(self.state.startup_fade, self.sample_rate));
+        }
+        let target = if self.state.muted.load(Ordering::Relaxed) {
+            0.
+        } else {
+            1.
+        };
+        if self.mute.target() != target {
+            self.mute.declick_to(target, self.sample_rate);
+        }
+        let volume = self.state.master_volume.load(Ordering::Relaxed);
+        let ramps_settled = self.mute.is_settled_at(1.)
+            && self.mix_gain.is_settled_at(1.)
+            && self.startup.is_settled_at(1.)
+            && self.automation.is_idle_at_unity();
+        if volume != 1. || !ramps_settled {
+            for frame in data.chunks_exact_mut(channels) {
+                let gain = volume
+                    * self.mute.next()
+                    * self.mix_gain.next()
+                    * self.startup.next()
+                    * self.automation.next(self.sample_rate);
+                frame.iter_mut().for_each(|sample| *sample *= gain);
+            }
+        }
+    }
+
+    /// Fades everything, including renderers bypassing the master, out on
+    /// [`MixerCommand::ClearAll`].
+    fn apply_clear_fade(&mut self, data: &mut [f32], channels: usize) {
+        if self.clear_fade.is_settled_at(1.) {
+            return;
+        }
+        for frame in data.chunks_exact_mut(channels) {
+            let gain = self.clear_fade.next();
+            frame.iter_mut().for_each(|sample| *sample *= gain);
+        }
+    }
+
+    /// Fades everything, including renderers bypassing the master, while
+    /// audio focus is lost.
+    fn apply_focus(&mut self, data: &mut [f32], channels: usize) {
+        let target = if self.state.focused.load(Ordering::Relaxed) {
+            1.
+        } else {
+            0.
+        };
+        if self.focus.target() != target {
+            self.focus
+                .set_target(target, seconds_to_frames(FOCUS_FADE_TIME, self.sample_rate));
+        }
+        if self.focus.is_settled_at(1.) {
+            return;
+        }
+        for frame in data.chunks_exact_mut(channels) {
+            let gain = self.focus.next();
+            frame.iter_mut().for_each(|sample| *sample *= gain);
+        }
+    }
+
+    fn finish_output(&mut self, data: &mut [f32], channels: usize) {
+        if self.dc_block {
+            self.block_dc(data, channels);
+        }
+        if data.iter().any(|sample| sample.abs() > 1.) {
+            self.state.clipping.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Single-pole high-pass at about 20 Hz.
+    fn block_dc(&mut self, data: &mut [f32], channels: usize) {
+        const CUTOFF: f32 = 20.;
+        let r = 1. - std::f32::consts::TAU * CUTOFF / self.sample_rate as f32;
+        self.dc_state.resize(channels, (0., 0.));
+        for frame in data.chunks_exact_mut(channels) {
+            for (sample, (x1, y1)) in frame.iter_mut().zip(&mut self.dc_state) {
+                let y = *sample - *x1 + r * *y1;
+                *x1 = *sample;
+                *y1 = y;
+                *sample = y;
+            }
+        }
+    }
+
+    /// Splits the mono sum of the output at about 250 Hz and 4 kHz with
+    /// single-pole crossovers and publishes the RMS of each band.
+    fn meter_bands(&mut self, data: &[f32], channels: usize) {
+        const LOW_CUTOFF: f32 = 250.;
+        const HIGH_CUTOFF: f32 = 4000.;
+        let coefficient = |cutoff: f32| {
+            1. - (-std::f32::consts::TAU * cutoff / self.sample_rate as f32).exp()
+        };
+        let (low_k, high_k) = (coefficient(LOW_CUTOFF), coefficient(HIGH_CUTOFF));
+        let (mut low, mut below_high) = self.band_state;
+        let mut sums = [0.; 3];
+        for frame in data.chunks_exact(channels) {
+            let x = frame.iter().sum::<f32>() / channels as f32;
+            low += low_k * (x - low);
+            below_high += high_k * (x - below_high);
+            let bands = [low, below_high - low, x - below_high];
+            for (sum, band) in sums.iter_mut().zip(bands) {
+                *sum += band * band;
+            }
+        }
+        self.band_state = (low, below_high);
+        let frames = (data.len() / channels).max(1) as f32;
+        for (band, sum) in self.state.bands.iter().zip(sums) {
+            band.store((sum / frames).sqrt(), Ordering::Relaxed);
+        }
+    }
+
+    /// Stops sounding renderers beyond `max_active`: lowest priority first,
+    /// then quietest, then oldest. Renderers whose [`Renderer::stop`] leaves
+    /// them sounding are skipped and keep playing.
+    fn enforce_max_active(&mut self) {
+        let sounding = |slot: &RendererSlot| slot.renderer.active_voices() > 0;
+        let mut active = self.renderers.iter().filter(|slot| sounding(slot)).count();
+        let mut excess = active.saturating_sub(self.max_active);
+        while excess > 0 {
+            let Some(slot) = self
+                .renderers
+                .iter_mut()
+                .filter(|slot| !slot.unstoppable && sounding(slot))
+                .min_by(|a, b| {
+                    a.renderer
+                        .priority()
+                        .cmp(&b.renderer.priority())
+                        .then(a.level.total_cmp(&b.level))
+                })
+            else {
+                break;
+            };
+            slot.renderer.stop();
+            if sounding(slot) {
+                slot.unstoppable = true;
+            } else {
+                active -= 1;
+                excess -= 1;
+            }
+        }
+        self.state.active_renderers.store(active, Ordering::Relaxed);
+    }
+
+    fn record_cpu_load(&self, start: Instant, frames: usize) {
+        if frames == 0 {
+            return;
+        }
+        let load = start.elapsed().as_secs_f64() * self.sample_rate as f64 / frames as f64;
+        let smoothed = self.state.cpu_load.load(Ordering::Relaxed) * 0.9 + load * 0.1;
+        self.state.cpu_load.store(smoothed, Ordering::Relaxed);
+    }
+
+    fn render(&mut self, data: &mut [f32], channels: usize) {
+        self.state
+            .block_frames
+            .store(data.len() / channels, Ordering::Relaxed);
+        let block_len = self.state.block_size * channels;
+        if block_len == 0 {
+            self.render_block(data, channels);
+            return;
+        }
+        if self.pending.len() != block_len {
+            // Channel count or block size changed; leftovers no longer fit.
+            self.pending.clear();
+            self.pending_start = 0;
+        }
+        let buffered = self.pending.len() - self.pending_start;
+        let mut filled = buffered.min(data.len());
+        data[..filled].copy_from_slice(&self.pending[self.pending_start..][..filled]);
+        self.pending_start += filled;
+        while data.len() - filled >= block_len {
+            self.render_block(&mut data[filled..filled + block_len], channels);
+            filled += block_len;
+        }
+        if filled < data.len() {
+            let mut pending = std::mem::take(&mut self.pending);
+            pending.resize(block_len, 0.);
+            self.render_block(&mut pending, channels);
+            let rest = data.len() - filled;
+            data[filled..].copy_from_slice(&pending[..rest]);
+            self.pending = pending;
+            self.pending_start = rest;
+        }
+    }
+
+    fn render_block(&mut self, data: &mut [f32], channels: usize) {
+        let start = Instant::now();
         self.consume_commands();
+        self.enforce_max_active();
         data.fill(0.);
 
-        self.renderers.retain_mut(|renderer| {
-            renderer.render_mono(self.sample_rate, data);
-            renderer.alive()
+        let sample_rate = self.sample_rate;
+        let solo = self.solo;
+        let frames = data.len() / channels;
+        if solo.is_some() || channels > 2 {
+            self.scratch.resize(data.len(), 0.);
+        }
+        let bypassing = self.renderers.iter().any(|slot| slot.bypass_master);
+        if bypassing {
+            self.bypass.clear();
+            self.bypass.resize(data.len(), 0.);
+        }
+        let capped = self.max_active != usize::MAX;
+        if capped || self.renderers.iter().any(|slot| !slot.sends.is_empty()) {
+            self.isolated.resize(data.len(), 0.);
+        }
+        for bus in &mut self.buses {
+            bus.buffer.clear();
+            bus.buffer.resize(data.len(), 0.);
+        }
+        let scratch = &mut self.scratch;
+        let bypass = &mut self.bypass;
+        let isolated = &mut self.isolated;
+        let buses = &mut self.buses;
+        self.renderers.retain_mut(|slot| {
+            let muted = solo.is_some_and(|solo| solo != slot.id);
+            let isolate = !muted && (capped || !slot.sends.is_empty());
+            let target = if isolate {
+                isolated.fill(0.);
+                &mut isolated[..]
+            } else if slot.bypass_master {
+                &mut bypass[..]
+            } else {
+                &mut *data
+            };
+            if channels > 2 {
+                let stereo = &mut scratch[..frames * 2];
+                stereo.fill(0.);
+                slot.renderer.render_stereo(sample_rate, stereo);
+                let (left, right) = slot.output_channels;
+                if !muted && left < channels && right < channels {
+                    let routed = target.chunks_exact_mut(channels).zip(stereo.chunks_exact(2));
+                    for (output, frame) in routed {
+                        output[left] += frame[0];
+                        output[right] += frame[1];
+                    }
+                }
+            } else {
+                let output = if muted {
+                    scratch.fill(0.);
+                    &mut scratch[..]
+                } else {
+                    target
+                };
+                if channels == 1 {
+                    slot.renderer.render_mono(sample_rate, output);
+                } else {
+                    slot.renderer.render_stereo(sample_rate, output);
+                }
+            }
+            if isolate {
+                let dry = if slot.bypass_master {
+                    &mut bypass[..]
+                } else {
+                    &mut *data
+                };
+                let mut sum_of_squares = 0.;
+                for (output, sample) in dry.iter_mut().zip(isolated.iter()) {
+                    *output += sample;
+                    sum_of_squares += sample * sample;
+                }
+                slot.level = (sum_of_squares / isolated.len().max(1) as f32).sqrt();
+                for &(id, level) in &slot.sends {
+                    if let Some(bus) = buses.iter_mut().find(|bus| bus.id == id) {
+                        for (output, sample) in bus.buffer.iter_mut().zip(isolated.iter()) {
+                            *output += sample * level;
+                        }
+                    }
+                }
+            }
+            slot.renderer.alive()
+        });
+        for bus in &mut self.buses {
+            bus.effect.process(sample_rate, channels, &mut bus.buffer);
+            for (output, sample) in data.iter_mut().zip(&bus.buffer) {
+                *output += sample;
+            }
+        }
+        self.post_process(data, channels);
+        if bypassing {
+            for (output, sample) in data.iter_mut().zip(&self.bypass) {
+                *output += sample;
+            }
+        }
+        self.apply_clear_fade(data, channels);
+        self.apply_focus(data, channels);
+        if channels == 2 && self.state.mono_monitor.load(Ordering::Relaxed) {
+            for frame in data.chunks_exact_mut(2) {
+                let mono = Frame(frame[0], frame[1]).avg();
+                frame.fill(mono);
+            }
+        }
+        // Bypassed audio is in `data` by now, so clipping detection covers it.
+        self.finish_output(data, channels);
+        if let Some(meter) = &mut self.loudness {
+            meter.process(data, channels, &self.state);
+        }
+        if self.state.band_metering.load(Ordering::Relaxed) {
+            self.meter_bands(data, channels);
+        }
+        self.finish_clear();
+        self.record_cpu_load(start, frames);
+    }
+
+    /// Stops and drops the renderers a [`MixerCommand::ClearAll`] faded out.
+    fn finish_clear(&mut self) {
+        let Some(last) = self.clearing else {
+            return;
+        };
+        if !self.clear_fade.is_settled_at(0.) {
+            return;
+        }
+        self.renderers.retain_mut(|slot| {
+            // Ids are handed out in increasing order.
+            let cleared = slot.id.0 <= last.0;
+            if cleared {
+                slot.renderer.stop();
+            }
+            !cleared
         });
+        self.clearing = None;
+        // Renderers added during the fade sound through it, so ramp back up
+        // rather than jumping.
+        self.clear_fade.declick_to(1., self.sample_rate);
+    }
+
+    pub fn render_mono(&mut self, data: &mut [f32]) {
+        self.render(data, 1);
     }
 
     pub fn render_stereo(&mut self, data: &mut [f32]) {
-        self.consume_commands();
-        data.fill(0.);
+        self.render(data, 2);
+    }
 
-        self.renderers.retain_mut(|renderer| {
-            renderer.render_stereo(self.sample_rate, data);
-            renderer.alive()
-        });
+    /// Renders into an interleaved buffer with `channels` channels, placing
+    /// each renderer on its configured output pair.
+    pub fn render_multichannel(&mut self, data: &mut [f32], channels: usize) {
+        self.render(data, channels);
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{backend::manual::ManualBackend, AudioClip, AudioManager, Frame, SfxParams};
+
+    #[test]
+    fn clear_all_ramps_down_bypassing_renderers() {
+        let backend = ManualBackend::new(48000, 2);
+        let mut manager = AudioManager::new(backend.clone()).unwrap();
+        let clip = AudioClip::from_raw(vec![Frame(1., 1.); 48000], 48000);
+        let params = SfxParams {
+            bypass_master: true,
+            ..Default::default()
+        };
+        let mut sfx = manager.create_sfx_with_params(clip, params).unwrap();
+        sfx.play(Default::default());
+        assert_eq!(backend.render(64)[63].0, 1.);
+
+        manager.clear_all().unwrap();
+        let frames = backend.render(4800);
+        assert!(frames[0].0 > 0.9);
+        assert!(frames.windows(2).all(|pair| pair[0].0 - pair[1].0 < 0.01));
+        assert_eq!(frames[4799].0, 0.);
+    }
+}
@@ -0,0 +1,56 @@
+/// Time over which gain switches such as mute are ramped to avoid clicks.
+pub(crate) const DECLICK_TIME: f64 = 0.005;
+
+/// Converts a duration to whole frames at the negotiated output `sample_rate`.
+/// Every seconds-based timing in the mixer and renderers goes through this.
+#[inline]
+pub(crate) fn seconds_to_frames(seconds: f64, sample_rate: u32) -> u32 {
+    (seconds.max(0.) * sample_rate as f64).round() as u32
+}
+
+/// Linear gain ramp towards a target, advanced once per frame.
+pub(crate) struct GainRamp {
+    current: f32,
+    target: f32,
+    step: f32,
+}
+
+impl GainRamp {
+    pub(crate) fn new(value: f32) -> Self {
+        Self {
+            current: value,
+            target: value,
+            step: 0.,
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn target(&self) -> f32 {
+        self.target
+    }
+
+    pub(crate) fn set_target(&mut self, target: f32, frames: u32) {
+        self.target = target;
+        self.step = (target - self.current).abs() / frames.max(1) as f32;
+    }
+
+    /// Ramps towards `target` over [`DECLICK_TIME`].
+    pub(crate) fn declick_to(&mut self, target: f32, sample_rate: u32) {
+        self.set_target(target, seconds_to_frames(DECLICK_TIME, sample_rate));
+    }
+
+    #[inline]
+    pub(crate) fn next(&mut self) -> f32 {
+        if self.current < self.target {
+            self.current = (self.current + self.step).min(self.target);
+        } else if self.current > self.target {
+            self.current = (self.current - self.step).max(self.target);
+        }
+        self.current
+    }
+
+    #[inline(always)]
+    pub(crate) fn is_settled_at(&self, value: f32) -> bool {
+        self.current == value && self.target == value
+    }
+}
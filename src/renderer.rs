@@ -1,11 +1,80 @@
 mod music;
-pub use music::{Music, MusicParams};
+pub use music::{Music, MusicParams, MusicState};
+
+mod layered;
+pub use layered::{LayeredMusic, LayeredMusicParams};
 
 mod sfx;
-pub use sfx::{Sfx, PlaySfxParams};
+pub use sfx::{PlayResult, PlaySfxParams, Sfx, SfxParams, VoiceId};
+
+mod push;
+pub use push::{PushSource, UnderrunFill};
+
+#[cfg(feature = "async")]
+mod finish;
+#[cfg(feature = "async")]
+pub use finish::Finished;
+
+/// Identifies a renderer added to an [`AudioManager`](crate::AudioManager).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RendererId(pub(crate) u64);
 
 pub trait Renderer: Send + Sync {
     fn alive(&self) -> bool;
     fn render_mono(&mut self, sample_rate: u32, data: &mut [f32]);
     fn render_stereo(&mut self, sample_rate: u32, data: &mut [f32]);
+
+    /// Number of voices currently sounding, used by normalized mix modes.
+    fn active_voices(&self) -> usize {
+        1
+    }
+
+    /// Renderers with lower priority are stopped first when the mixer's
+    /// polyphony cap is hit.
+    fn priority(&self) -> u8 {
+        0
+    }
+
+    /// Silences every voice, called when the mixer's polyphony cap is hit.
+    /// The default does nothing; renderers still sounding afterwards are
+    /// left playing and never picked again.
+    fn stop(&mut self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{backend::manual::ManualBackend, AudioClip, AudioManager, Frame, MusicParams};
+
+    /// One second at 22050 Hz, which must last one second at 48000 Hz.
+    fn clip() -> AudioClip {
+        AudioClip::from_raw(vec![Frame(0.5, 0.5); 22050], 22050)
+    }
+
+    fn sounding_frames(backend: &ManualBackend) -> usize {
+        backend
+            .render(48000 + 4800)
+            .iter()
+            .filter(|frame| frame.0 != 0.)
+            .count()
+    }
+
+    #[test]
+    fn music_plays_at_clip_rate() {
+        let backend = ManualBackend::new(48000, 2);
+        let mut manager = AudioManager::new(backend.clone()).unwrap();
+        let mut music = manager
+            .create_music(clip(), MusicParams::default())
+            .unwrap();
+        music.play().unwrap();
+        assert_eq!(sounding_frames(&backend), 48000);
+    }
+
+    #[test]
+    fn sfx_plays_at_clip_rate() {
+        let backend = ManualBackend::new(48000, 2);
+        let mut manager = AudioManager::new(backend.clone()).unwrap();
+        let mut sfx = manager.create_sfx(clip(), None).unwrap();
+        sfx.play(Default::default());
+        assert_eq!(sounding_frames(&backend), 48000);
+    }
 }
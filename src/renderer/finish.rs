@@ -0,0 +1,54 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::Mutex,
+    task::{Context, Poll, Waker},
+};
+
+/// Waker slot the audio thread signals when a renderer finishes.
+#[derive(Default)]
+pub(crate) struct FinishWaker(Mutex<Option<Waker>>);
+
+impl FinishWaker {
+    /// Never blocks: if the control thread is registering a waker right now,
+    /// it re-checks the finished condition afterwards anyway.
+    pub(crate) fn wake(&self) {
+        if let Ok(mut waker) = self.0.try_lock() {
+            if let Some(waker) = waker.take() {
+                waker.wake();
+            }
+        }
+    }
+
+    fn register(&self, waker: &Waker) {
+        *self.0.lock().unwrap() = Some(waker.clone());
+    }
+}
+
+/// Future resolving once a `Music` or `Sfx` has finished playing.
+pub struct Finished<'a, F: Fn() -> bool> {
+    waker: &'a FinishWaker,
+    is_finished: F,
+}
+
+impl<'a, F: Fn() -> bool> Finished<'a, F> {
+    pub(crate) fn new(waker: &'a FinishWaker, is_finished: F) -> Self {
+        Self { waker, is_finished }
+    }
+}
+
+impl<F: Fn() -> bool + Unpin> Future for Finished<'_, F> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if (self.is_finished)() {
+            return Poll::Ready(());
+        }
+        self.waker.register(cx.waker());
+        if (self.is_finished)() {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
@@ -0,0 +1,299 @@
+use crate::{
+    buffer_is_full,
+    ramp::{seconds_to_frames, GainRamp},
+    AudioClip, Frame, Renderer, RendererId, ResampleQuality,
+};
+use anyhow::{bail, Context, Result};
+use atomic_float::AtomicF64;
+use ringbuf::{HeapConsumer, HeapProducer, HeapRb};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Weak,
+};
+
+#[derive(Debug, Clone)]
+pub struct LayeredMusicParams {
+    pub looping: bool,
+    pub amplifier: f32,
+    /// Seconds over which [`LayeredMusic::set_layer_gain`] ramps a layer
+    /// while playing.
+    pub layer_fade_time: f64,
+    pub command_buffer_size: usize,
+    pub resample_quality: ResampleQuality,
+    /// See [`AudioManager::set_bypass_master`](crate::AudioManager::set_bypass_master).
+    pub bypass_master: bool,
+}
+impl Default for LayeredMusicParams {
+    fn default() -> Self {
+        Self {
+            looping: true,
+            amplifier: 1.,
+            layer_fade_time: 1.,
+            command_buffer_size: 16,
+            resample_quality: ResampleQuality::default(),
+            bypass_master: false,
+        }
+    }
+}
+
+struct SharedState {
+    position: AtomicF64,
+    paused: AtomicBool,
+    finished: AtomicBool,
+}
+
+enum LayeredMusicCommand {
+    Pause,
+    Resume,
+    SeekTo(f64),
+    SetLayerGain(usize, f32),
+}
+
+pub(crate) struct LayeredMusicRenderer {
+    layers: Vec<AudioClip>,
+    gains: Vec<GainRamp>,
+    settings: LayeredMusicParams,
+    state: Weak<SharedState>,
+    cons: HeapConsumer<LayeredMusicCommand>,
+    /// Length of the longest layer; shorter ones are silent past their end.
+    length: f64,
+    paused: bool,
+    finished: bool,
+    /// Shared playhead of every layer, in output frames.
+    index: usize,
+    last_sample_rate: u32,
+}
+impl LayeredMusicRenderer {
+    fn prepare(&mut self, sample_rate: u32) {
+        if self.last_sample_rate != sample_rate {
+            let factor = sample_rate as f64 / self.last_sample_rate as f64;
+            self.index = (self.index as f64 * factor).round() as _;
+            self.last_sample_rate = sample_rate;
+        }
+        while let Some(cmd) = self.cons.pop() {
+            match cmd {
+                LayeredMusicCommand::Pause => self.paused = true,
+                LayeredMusicCommand::Resume => {
+                    if self.finished {
+                        self.finished = false;
+                        self.index = 0;
+                    }
+                    self.paused = false;
+                }
+                LayeredMusicCommand::SeekTo(position) => {
+                    let position = position.clamp(0., self.length);
+                    self.index = (position * sample_rate as f64).round() as usize;
+                    self.finished = false;
+                }
+                LayeredMusicCommand::SetLayerGain(layer, gain) => {
+                    let ramp = &mut self.gains[layer];
+                    if self.paused {
+                        *ramp = GainRamp::new(gain);
+                    } else {
+                        let frames = seconds_to_frames(self.settings.layer_fade_time, sample_rate);
+                        ramp.set_target(gain, frames);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Sums every audible layer at the shared playhead, or `None` once a
+    /// non-looping piece has ended.
+    #[inline]
+    fn frame(&mut self, sample_rate: u32) -> Option<Frame> {
+        let mut position = self.index as f64 / sample_rate as f64;
+        if position >= self.length {
+            if !self.settings.looping || self.length <= 0. {
+                self.paused = true;
+                self.finished = true;
+                return None;
+            }
+            position %= self.length;
+            self.index = (position * sample_rate as f64).round() as _;
+        }
+        self.index += 1;
+        let mut frame = Frame::default();
+        for (clip, gain) in self.layers.iter().zip(&mut self.gains) {
+            if gain.is_settled_at(0.) {
+                continue;
+            }
+            let gain = gain.next();
+            if let Some(sample) = clip.sample_with(position, self.settings.resample_quality) {
+                frame = frame + sample * gain;
+            }
+        }
+        Some(frame * self.settings.amplifier)
+    }
+
+    fn publish(&self, sample_rate: u32) {
+        if let Some(state) = self.state.upgrade() {
+            state
+                .position
+                .store(self.index as f64 / sample_rate as f64, Ordering::SeqCst);
+            state.paused.store(self.paused, Ordering::SeqCst);
+            state.finished.store(self.finished, Ordering::SeqCst);
+        }
+    }
+}
+
+impl Renderer for LayeredMusicRenderer {
+    fn alive(&self) -> bool {
+        self.state.strong_count() != 0
+    }
+
+    fn active_voices(&self) -> usize {
+        !self.paused as usize
+    }
+
+    fn stop(&mut self) {
+        self.prepare(self.last_sample_rate);
+        self.paused = true;
+        self.publish(self.last_sample_rate);
+    }
+
+    fn render_mono(&mut self, sample_rate: u32, data: &mut [f32]) {
+        self.prepare(sample_rate);
+        if !self.paused {
+            for sample in data.iter_mut() {
+                let Some(frame) = self.frame(sample_rate) else {
+                    break;
+                };
+                *sample += frame.avg();
+            }
+        }
+        self.publish(sample_rate);
+    }
+
+    fn render_stereo(&mut self, sample_rate: u32, data: &mut [f32]) {
+        self.prepare(sample_rate);
+        if !self.paused {
+            for sample in data.chunks_exact_mut(2) {
+                let Some(frame) = self.frame(sample_rate) else {
+                    break;
+                };
+                sample[0] += frame.0;
+                sample[1] += frame.1;
+            }
+        }
+        self.publish(sample_rate);
+    }
+}
+
+/// Several stems sharing one playhead, e.g. drums, bass and melody of an
+/// adaptive score. Layers are mixed sample-accurately, so unlike separate
+/// [`Music`](crate::Music) instances they can never drift apart.
+pub struct LayeredMusic {
+    id: RendererId,
+    arc: Arc<SharedState>,
+    prod: HeapProducer<LayeredMusicCommand>,
+    layer_count: usize,
+}
+impl LayeredMusic {
+    pub(crate) fn new(
+        id: RendererId,
+        layers: Vec<AudioClip>,
+        settings: LayeredMusicParams,
+    ) -> Result<(LayeredMusic, LayeredMusicRenderer)> {
+        let Some(first) = layers.first() else {
+            bail!("layered music needs at least one layer");
+        };
+        let sample_rate = first.sample_rate();
+        if let Some(clip) = layers.iter().find(|clip| clip.sample_rate() != sample_rate) {
+            bail!(
+                "layer sample rate {} does not match {}",
+                clip.sample_rate(),
+                sample_rate
+            );
+        }
+        let (prod, cons) = HeapRb::new(settings.command_buffer_size).split();
+        let arc = Arc::new(SharedState {
+            position: AtomicF64::new(0.),
+            paused: AtomicBool::new(true),
+            finished: AtomicBool::new(false),
+        });
+        let layer_count = layers.len();
+        let length = layers.iter().map(AudioClip::length).fold(0., f64::max);
+        let renderer = LayeredMusicRenderer {
+            gains: (0..layer_count).map(|_| GainRamp::new(1.)).collect(),
+            layers,
+            settings,
+            state: Arc::downgrade(&arc),
+            cons,
+            length,
+            paused: true,
+            finished: false,
+            index: 0,
+            last_sample_rate: 1,
+        };
+        Ok((
+            Self {
+                id,
+                arc,
+                prod,
+                layer_count,
+            },
+            renderer,
+        ))
+    }
+
+    #[inline(always)]
+    pub fn id(&self) -> RendererId {
+        self.id
+    }
+
+    #[inline(always)]
+    pub fn layer_count(&self) -> usize {
+        self.layer_count
+    }
+
+    /// Starts or resumes playback, from the beginning if the piece finished.
+    pub fn play(&mut self) -> Result<()> {
+        self.prod
+            .push(LayeredMusicCommand::Resume)
+            .map_err(buffer_is_full)
+            .context("play layered music")
+    }
+
+    pub fn pause(&mut self) -> Result<()> {
+        self.prod
+            .push(LayeredMusicCommand::Pause)
+            .map_err(buffer_is_full)
+            .context("pause")
+    }
+
+    pub fn paused(&self) -> bool {
+        self.arc.paused.load(Ordering::SeqCst)
+    }
+
+    /// Moves the shared playhead to `position` seconds, clamped to the
+    /// longest layer.
+    pub fn seek_to(&mut self, position: f64) -> Result<()> {
+        self.prod
+            .push(LayeredMusicCommand::SeekTo(position))
+            .map_err(buffer_is_full)
+            .context("seek to")
+    }
+
+    /// Fades `layer` to `amp` over [`LayeredMusicParams::layer_fade_time`].
+    /// While paused the gain is applied immediately. Every layer starts at 1.
+    pub fn set_layer_gain(&mut self, layer: usize, amp: f32) -> Result<()> {
+        if layer >= self.layer_count {
+            bail!("layer {layer} out of range ({} layers)", self.layer_count);
+        }
+        self.prod
+            .push(LayeredMusicCommand::SetLayerGain(layer, amp))
+            .map_err(buffer_is_full)
+            .context("set layer gain")
+    }
+
+    /// Playhead in seconds as of the last rendered block.
+    pub fn position(&self) -> f64 {
+        self.arc.position.load(Ordering::SeqCst)
+    }
+
+    /// The playhead reached the end of a non-looping piece.
+    pub fn is_finished(&self) -> bool {
+        self.arc.finished.load(Ordering::SeqCst)
+    }
+}
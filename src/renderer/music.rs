@@ -1,39 +1,125 @@
-use crate::{buffer_is_full, AudioClip, Frame, Renderer};
-use anyhow::{Context, Result};
-use atomic_float::AtomicF64;
+#[cfg(feature = "async")]
+use super::finish::{FinishWaker, Finished};
+use crate::{
+    buffer_is_full,
+    mixer::MixerState,
+    ramp::{seconds_to_frames, GainRamp},
+    tag::{MusicTag, Tagged},
+    AudioClip, Frame, Renderer, RendererId, ResampleQuality,
+};
+use anyhow::{bail, Context, Result};
+use atomic_float::{AtomicF32, AtomicF64};
 use ringbuf::{HeapConsumer, HeapProducer, HeapRb};
-use std::sync::{
-    atomic::{AtomicBool, Ordering},
-    Arc, Weak,
+use std::{
+    hint, ptr,
+    sync::{
+        atomic::{self, AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex, Weak,
+    },
+    time::Instant,
 };
 
+/// Upper bound on how far [`Music::position`] extrapolates past the last
+/// published block, so a stalled stream doesn't run the playhead away.
+const MAX_POSITION_EXTRAPOLATION: f64 = 0.2;
+
 #[derive(Debug, Clone)]
 pub struct MusicParams {
     pub loop_mix_time: f64,
+    /// With a non-negative `loop_mix_time`, how many times the clip plays in
+    /// total before finishing. `None` loops forever.
+    pub loop_count: Option<u32>,
     pub amplifier: f32,
     pub playback_rate: f64,
     pub command_buffer_size: usize,
+    /// Position in seconds the playhead starts at.
+    pub start_position: f64,
+    pub resample_quality: ResampleQuality,
+    /// Higher priorities are stopped last when the mixer's polyphony cap is hit.
+    pub priority: u8,
+    /// Nudges the gain against `playback_rate` so slowed-down music doesn't
+    /// sound quieter and sped-up music doesn't get louder: about +1.5 dB at
+    /// half speed and -1.5 dB at double speed.
+    pub compensate_rate_gain: bool,
+    /// Keep the renderer in the mixer once a non-looping clip finishes, so
+    /// [`Music::restart`] can replay it. Otherwise it leaves the mixer when
+    /// finished and the handle becomes inert, as after [`Music::stop`].
+    pub keep_alive: bool,
+    /// See [`AudioManager::set_bypass_master`](crate::AudioManager::set_bypass_master).
+    pub bypass_master: bool,
 }
 impl Default for MusicParams {
     fn default() -> Self {
         Self {
             loop_mix_time: -1.,
+            loop_count: None,
             amplifier: 1.,
             playback_rate: 1.,
             command_buffer_size: 16,
+            start_position: 0.,
+            resample_quality: ResampleQuality::default(),
+            priority: 0,
+            compensate_rate_gain: false,
+            keep_alive: true,
+            bypass_master: false,
         }
     }
 }
 
+/// A consistent view of a [`Music`], published by the renderer once per block.
+#[derive(Debug, Clone, Copy)]
+pub struct MusicState {
+    pub position: f64,
+    pub amplifier: f32,
+    pub playback_rate: f64,
+    pub paused: bool,
+    /// The playhead reached the end of a non-looping clip.
+    pub finished: bool,
+}
+
 struct SharedState {
+    /// Seqlock guarding the fields below; odd while the renderer is writing.
+    seq: AtomicU64,
     position: AtomicF64,
+    /// Playhead in frames of the clip, see [`Music::position_frames`].
+    frame: AtomicU64,
+    amplifier: AtomicF32,
+    playback_rate: AtomicF64,
     paused: AtomicBool,
+    finished: AtomicBool,
+    /// The renderer finished without `keep_alive` and left the mixer.
+    removed: AtomicBool,
+    /// Seconds since `epoch` at which the block above was published.
+    published_at: AtomicF64,
+    epoch: Instant,
+
+    /// Set on the first [`Music::current_level`] call; metering is skipped until then.
+    metering: AtomicBool,
+    /// RMS of the renderer's own output over the last block.
+    level: AtomicF32,
+
+    #[cfg(feature = "async")]
+    finish_waker: FinishWaker,
 }
 impl Default for SharedState {
     fn default() -> Self {
         Self {
+            seq: AtomicU64::new(0),
             position: AtomicF64::default(),
+            frame: AtomicU64::new(0),
+            amplifier: AtomicF32::new(1.),
+            playback_rate: AtomicF64::new(1.),
             paused: AtomicBool::new(true),
+            finished: AtomicBool::new(false),
+            removed: AtomicBool::new(false),
+            published_at: AtomicF64::new(0.),
+            epoch: Instant::now(),
+
+            metering: AtomicBool::new(false),
+            level: AtomicF32::new(0.),
+
+            #[cfg(feature = "async")]
+            finish_waker: FinishWaker::default(),
         }
     }
 }
@@ -42,17 +128,49 @@ enum MusicCommand {
     Pause,
     Resume,
     SetAmplifier(f32),
+    SetAmplifierImmediate(f32),
     SeekTo(f64),
+    SeekToFrame(u64),
     SetLowPass(f32),
     FadeIn(f64),
     FadeOut(f64),
+    SetMuted(bool),
+    Restart,
+    SetClip(AudioClip),
+    PlayRegion(f64, f64),
+    SetBalance(f32, f32),
+    SetLoopRegion(Option<(f64, f64)>),
+    SetLoopRegionBeats(Option<(f64, f64)>),
+    Remove,
+}
+/// Whether the pass after `loops_done` completed ones wraps around into another.
+#[inline]
+fn loops_again(settings: &MusicParams, loops_done: u32) -> bool {
+    settings.loop_mix_time >= 0.
+        && settings
+            .loop_count
+            .is_none_or(|count| loops_done + 1 < count)
 }
+
+/// Gain keeping the perceived level roughly constant at `playback_rate`.
+#[inline]
+fn rate_compensation(playback_rate: f64) -> f32 {
+    if playback_rate > 0. {
+        playback_rate.powf(-0.25) as f32
+    } else {
+        1.
+    }
+}
+
 pub(crate) struct MusicRenderer {
     clip: AudioClip,
     settings: MusicParams,
     state: Weak<SharedState>,
     cons: HeapConsumer<MusicCommand>,
     paused: bool,
+    finished: bool,
+    /// Completed passes through the clip, compared against `loop_count`.
+    loops_done: u32,
     index: usize,
     last_sample_rate: u32,
     low_pass: f32,
@@ -60,6 +178,32 @@ pub(crate) struct MusicRenderer {
 
     fade_time: i32,
     fade_current: i32,
+
+    mute: GainRamp,
+    /// Follows `settings.amplifier` with a short ramp to avoid zipper noise.
+    amplifier: GainRamp,
+
+    /// Clip being crossfaded out after [`Music::set_clip`].
+    previous_clip: Option<AudioClip>,
+    clip_fade: GainRamp,
+
+    /// Position at which [`Music::play_region`] playback finishes.
+    stop_at: Option<f64>,
+    /// Left and right output gains.
+    balance: (f32, f32),
+    /// Set from [`MusicParams::compensate_rate_gain`].
+    rate_gain: f32,
+    /// A/B loop bounds in seconds, jumped between when the playhead crosses
+    /// the end while armed.
+    loop_region: Option<(f64, f64)>,
+    /// Whether the playhead was before the loop end, so crossing it loops.
+    loop_armed: bool,
+    /// Loop bounds in beats, converted into `loop_region` every block
+    /// against the master tempo.
+    loop_beats: Option<(f64, f64)>,
+    mixer_state: Arc<MixerState>,
+    /// Set by [`Music::stop`]; the renderer leaves the mixer once silent.
+    removing: bool,
 }
 impl MusicRenderer {
     fn prepare(&mut self, sample_rate: u32) {
@@ -70,7 +214,7 @@ impl MusicRenderer {
             self.fade_time = (self.fade_time as f32 * factor).round() as _;
             self.fade_current = (self.fade_current as f32 * factor).round() as _;
         }
-        for cmd in self.cons.pop_iter() {
+        while let Some(cmd) = self.cons.pop() {
             match cmd {
                 MusicCommand::Pause => {
                     self.paused = true;
@@ -86,11 +230,18 @@ impl MusicRenderer {
                 }
                 MusicCommand::SetAmplifier(amp) => {
                     self.settings.amplifier = amp;
+                    if self.paused {
+                        self.amplifier = GainRamp::new(amp);
+                    } else {
+                        self.amplifier.declick_to(amp, sample_rate);
+                    }
                 }
-                MusicCommand::SeekTo(position) => {
-                    self.index = (position * sample_rate as f64 / self.settings.playback_rate)
-                        .round() as usize;
+                MusicCommand::SetAmplifierImmediate(amp) => {
+                    self.settings.amplifier = amp;
+                    self.amplifier = GainRamp::new(amp);
                 }
+                MusicCommand::SeekTo(position) => self.seek(position, sample_rate),
+                MusicCommand::SeekToFrame(frame) => self.seek_frame(frame, sample_rate),
                 MusicCommand::SetLowPass(low_pass) => {
                     self.low_pass = low_pass;
                 }
@@ -101,31 +252,157 @@ impl MusicRenderer {
                             state.paused.store(false, Ordering::SeqCst);
                         }
                     }
-                    self.fade_time = (time * sample_rate as f64).round() as _;
+                    self.fade_time = seconds_to_frames(time, sample_rate) as _;
                     self.fade_current = 0;
                 }
                 MusicCommand::FadeOut(time) => {
-                    self.fade_time = (-time * sample_rate as f64).round() as _;
+                    self.fade_time = -(seconds_to_frames(time, sample_rate) as i32);
                     self.fade_current = 0;
                 }
+                MusicCommand::SetMuted(muted) => {
+                    self.mute.declick_to(if muted { 0. } else { 1. }, sample_rate);
+                }
+                MusicCommand::Restart => {
+                    self.finished = false;
+                    self.loops_done = 0;
+                    self.stop_at = None;
+                    self.index = 0;
+                    self.arm_loop_region(0.);
+                    self.paused = false;
+                    if let Some(state) = self.state.upgrade() {
+                        state.paused.store(false, Ordering::SeqCst);
+                    }
+                }
+                MusicCommand::Remove => {
+                    self.removing = true;
+                    self.mute.declick_to(0., sample_rate);
+                }
+                MusicCommand::SetClip(clip) => {
+                    self.previous_clip = Some(std::mem::replace(&mut self.clip, clip));
+                    self.clip_fade = GainRamp::new(0.);
+                    self.clip_fade.declick_to(1., sample_rate);
+                }
+                MusicCommand::SetBalance(left, right) => self.balance = (left, right),
+                MusicCommand::SetLoopRegion(region) => {
+                    self.loop_beats = None;
+                    self.loop_region = region;
+                    let delta = self.settings.playback_rate / sample_rate as f64;
+                    self.arm_loop_region(self.position(delta));
+                }
+                MusicCommand::SetLoopRegionBeats(beats) => {
+                    self.loop_beats = beats;
+                    self.loop_region = self.beats_to_seconds(beats);
+                    let delta = self.settings.playback_rate / sample_rate as f64;
+                    self.arm_loop_region(self.position(delta));
+                }
+                MusicCommand::PlayRegion(start, end) => {
+                    self.seek(start, sample_rate);
+                    if !self.finished {
+                        self.stop_at = Some(end);
+                        self.paused = false;
+                        if let Some(state) = self.state.upgrade() {
+                            state.paused.store(false, Ordering::SeqCst);
+                        }
+                    }
+                }
             }
         }
+        if self.loop_beats.is_some() {
+            self.loop_region = self.beats_to_seconds(self.loop_beats);
+        }
+    }
+
+    fn beats_to_seconds(&self, beats: Option<(f64, f64)>) -> Option<(f64, f64)> {
+        let bpm = self.mixer_state.bpm.load(Ordering::Relaxed);
+        let (start, end) = beats?;
+        (bpm > 0.).then(|| (start * 60. / bpm, end * 60. / bpm))
+    }
+
+    fn seek(&mut self, position: f64, sample_rate: u32) {
+        let position = position.clamp(0., self.clip.length());
+        let index = (position * sample_rate as f64 / self.settings.playback_rate).round();
+        self.place_playhead(index as usize, position);
+    }
+
+    fn seek_frame(&mut self, frame: u64, sample_rate: u32) {
+        let frame = frame.min(self.clip.frame_count() as u64);
+        let index = (frame as f64 / self.clip_frames_per_index(sample_rate)).round();
+        self.place_playhead(index as usize, frame as f64 / self.clip.sample_rate() as f64);
+    }
+
+    /// Frames of the clip the playhead advances per output frame. Exactly `1`
+    /// when the clip plays at the output rate and a playback rate of 1, so
+    /// frame positions convert without rounding.
+    #[inline]
+    fn clip_frames_per_index(&self, sample_rate: u32) -> f64 {
+        self.settings.playback_rate * self.clip.sample_rate() as f64 / sample_rate as f64
+    }
+
+    /// Moves the playhead to output frame `index`, at `position` seconds.
+    fn place_playhead(&mut self, index: usize, position: f64) {
+        let length = self.clip.length();
+        self.index = index;
+        self.stop_at = None;
+        self.arm_loop_region(position);
+        self.finished = !self.looping() && position >= length;
+        if self.finished {
+            self.paused = true;
+            if let Some(state) = self.state.upgrade() {
+                state.paused.store(true, Ordering::SeqCst);
+            }
+        }
+    }
+
+    /// A playhead already past the loop end keeps playing until it is moved
+    /// back before it.
+    fn arm_loop_region(&mut self, position: f64) {
+        self.loop_armed = self.loop_region.is_some_and(|(_, end)| position < end);
+    }
+
+    #[inline]
+    fn looping(&self) -> bool {
+        loops_again(&self.settings, self.loops_done)
     }
 
     #[inline]
     fn frame(&mut self, position: f64, delta: f64) -> Option<Frame> {
+        if self.stop_at.is_some_and(|end| position >= end) {
+            self.stop_at = None;
+            self.paused = true;
+            self.finished = true;
+            return None;
+        }
+        let position = match self.loop_region {
+            Some((start, end)) if self.loop_armed && position >= end && end > start => {
+                let position = start + (position - end) % (end - start);
+                self.index = (position / delta).round() as _;
+                position
+            }
+            _ => position,
+        };
+        let looping = self.looping();
         let s = &self.settings;
-        if let Some(mut frame) = self.clip.sample(position) {
-            if s.loop_mix_time >= 0. {
+        if let Some(mut frame) = self.clip.sample_with(position, s.resample_quality) {
+            if looping {
                 let pos = position + s.loop_mix_time - self.clip.length();
                 if pos >= 0. {
-                    if let Some(new_frame) = self.clip.sample(pos) {
+                    if let Some(new_frame) = self.clip.sample_with(pos, s.resample_quality) {
                         frame = frame + new_frame;
                     }
                 }
             }
+            if let Some(previous) = &self.previous_clip {
+                let mix = self.clip_fade.next();
+                let old = previous
+                    .sample_with(position, s.resample_quality)
+                    .unwrap_or_default();
+                frame = frame * mix + old * (1. - mix);
+                if self.clip_fade.is_settled_at(1.) {
+                    self.previous_clip = None;
+                }
+            }
             self.index += 1;
-            let mut amp = s.amplifier;
+            let mut amp = self.amplifier.next();
             if self.fade_time != 0 {
                 if self.fade_time > 0 {
                     self.fade_current += 1;
@@ -148,17 +425,20 @@ impl MusicRenderer {
                     }
                 }
             }
-            Some(frame * amp)
-        } else if s.loop_mix_time >= 0. {
+            Some(frame * (amp * self.mute.next()))
+        } else if looping {
             let position = position - self.clip.length() + s.loop_mix_time;
             self.index = (position / delta).round() as _;
-            Some(if let Some(frame) = self.clip.sample(position) {
-                frame * s.amplifier
+            self.loops_done = self.loops_done.saturating_add(1);
+            self.loop_armed = self.loop_region.is_some_and(|(_, end)| position < end);
+            Some(if let Some(frame) = self.clip.sample_with(position, s.resample_quality) {
+                frame * (self.amplifier.next() * self.mute.next())
             } else {
                 Frame::default()
             })
         } else {
             self.paused = true;
+            self.finished = true;
             None
         }
     }
@@ -168,77 +448,202 @@ impl MusicRenderer {
         self.index as f64 * delta
     }
 
+    fn publish(&self, delta: f64) {
+        let Some(state) = self.state.upgrade() else {
+            return;
+        };
+        let seq = state.seq.load(Ordering::Relaxed);
+        state.seq.store(seq + 1, Ordering::Relaxed);
+        atomic::fence(Ordering::Release);
+        state.position.store(self.position(delta), Ordering::Relaxed);
+        let frame = self.index as f64 * self.clip_frames_per_index(self.last_sample_rate);
+        state.frame.store(frame.round() as u64, Ordering::Relaxed);
+        state
+            .amplifier
+            .store(self.settings.amplifier, Ordering::Relaxed);
+        state
+            .playback_rate
+            .store(self.settings.playback_rate, Ordering::Relaxed);
+        state.paused.store(self.paused, Ordering::Relaxed);
+        state.finished.store(self.finished, Ordering::Relaxed);
+        state
+            .published_at
+            .store(state.epoch.elapsed().as_secs_f64(), Ordering::Relaxed);
+        state.seq.store(seq + 2, Ordering::Release);
+        if self.finished && !self.settings.keep_alive {
+            state.removed.store(true, Ordering::Release);
+        }
+        #[cfg(feature = "async")]
+        if self.finished {
+            state.finish_waker.wake();
+        }
+    }
+
+    fn metering(&self) -> bool {
+        self.state
+            .upgrade()
+            .is_some_and(|state| state.metering.load(Ordering::Relaxed))
+    }
+
+    fn publish_level(&self, sum_of_squares: f32, samples: usize) {
+        if let Some(state) = self.state.upgrade() {
+            let level = (sum_of_squares / samples.max(1) as f32).sqrt();
+            state.level.store(level, Ordering::Relaxed);
+        }
+    }
+
     #[inline(always)]
     fn update_and_get(&mut self, frame: Frame) -> Frame {
         self.last_output = self.last_output * self.low_pass + frame * (1. - self.low_pass);
-        self.last_output
+        Frame(
+            self.last_output.0 * self.balance.0,
+            self.last_output.1 * self.balance.1,
+        ) * self.rate_gain
     }
 }
 
 impl Renderer for MusicRenderer {
     fn alive(&self) -> bool {
         self.state.strong_count() != 0
+            && !(self.removing && (self.paused || self.mute.is_settled_at(0.)))
+            && (self.settings.keep_alive || !self.finished)
+    }
+
+    fn active_voices(&self) -> usize {
+        !self.paused as usize
+    }
+
+    fn priority(&self) -> u8 {
+        self.settings.priority
+    }
+
+    fn stop(&mut self) {
+        self.prepare(self.last_sample_rate);
+        self.paused = true;
+        self.publish(1. / self.last_sample_rate as f64 * self.settings.playback_rate);
     }
 
     fn render_mono(&mut self, sample_rate: u32, data: &mut [f32]) {
         self.prepare(sample_rate);
+        let delta = 1. / sample_rate as f64 * self.settings.playback_rate;
+        let metering = self.metering();
+        let mut sum_of_squares = 0.;
         if !self.paused {
-            let delta = 1. / sample_rate as f64 * self.settings.playback_rate as f64;
             let mut position = self.index as f64 * delta;
             for sample in data.iter_mut() {
                 if let Some(frame) = self.frame(position, delta) {
-                    *sample += self.update_and_get(frame).avg();
+                    let value = self.update_and_get(frame).avg();
+                    *sample += value;
+                    if metering {
+                        sum_of_squares += value * value;
+                    }
                 } else {
                     break;
                 }
                 position += delta;
             }
-            if let Some(state) = self.state.upgrade() {
-                state
-                    .position
-                    .store(self.position(delta), Ordering::SeqCst);
-            }
+        }
+        self.publish(delta);
+        if metering {
+            self.publish_level(sum_of_squares, data.len());
         }
     }
 
     fn render_stereo(&mut self, sample_rate: u32, data: &mut [f32]) {
         self.prepare(sample_rate);
+        let delta = 1. / sample_rate as f64 * self.settings.playback_rate;
+        let metering = self.metering();
+        let mut sum_of_squares = 0.;
         if !self.paused {
-            let delta = 1. / sample_rate as f64 * self.settings.playback_rate as f64;
             let mut position = self.index as f64 * delta;
             for sample in data.chunks_exact_mut(2) {
                 if let Some(frame) = self.frame(position, delta) {
                     let frame = self.update_and_get(frame);
                     sample[0] += frame.0;
                     sample[1] += frame.1;
+                    if metering {
+                        sum_of_squares += frame.0 * frame.0 + frame.1 * frame.1;
+                    }
                 } else {
                     break;
                 }
                 position += delta;
             }
-            if let Some(state) = self.state.upgrade() {
-                state
-                    .position
-                    .store(self.position(delta), Ordering::SeqCst);
-            }
+        }
+        self.publish(delta);
+        if metering {
+            self.publish_level(sum_of_squares, data.len());
         }
     }
 }
 
+/// Command side of a [`Music`], shared by all of its clones.
+struct Control {
+    prod: HeapProducer<MusicCommand>,
+    /// Length in seconds of the current clip.
+    length: f64,
+    /// Set by [`Music::stop`], after which commands are dropped.
+    stopped: bool,
+}
+
+/// Handle controlling a music renderer.
+///
+/// Clones control the same renderer, which stays in the mixer until every
+/// handle has been dropped.
+#[derive(Clone)]
+#[repr(C)]
 pub struct Music {
+    /// First, where the C API reads it.
+    tag: MusicTag,
+    id: RendererId,
     arc: Arc<SharedState>,
-    prod: HeapProducer<MusicCommand>,
+    control: Arc<Mutex<Control>>,
+    /// Sample rate of the current clip, which replacements must match.
+    sample_rate: u32,
+}
+impl Tagged for Music {
+    const MAGIC: u32 = MusicTag::MAGIC;
+
+    unsafe fn tag_ptr(ptr: *const Self) -> *const u32 {
+        ptr::addr_of!((*ptr).tag).cast()
+    }
 }
 impl Music {
-    pub(crate) fn new(clip: AudioClip, settings: MusicParams) -> (Music, MusicRenderer) {
-        let (prod, cons) = HeapRb::new(settings.command_buffer_size).split();
-        let arc = Arc::default();
+    pub(crate) fn new(
+        id: RendererId,
+        clip: AudioClip,
+        settings: MusicParams,
+        mixer_state: Arc<MixerState>,
+    ) -> (Music, MusicRenderer) {
+        let (mut prod, cons) = HeapRb::new(settings.command_buffer_size).split();
+        let sample_rate = clip.sample_rate();
+        let length = clip.length();
+        let arc: Arc<SharedState> = Arc::new(SharedState {
+            amplifier: AtomicF32::new(settings.amplifier),
+            playback_rate: AtomicF64::new(settings.playback_rate),
+            ..Default::default()
+        });
+        if settings.start_position != 0. {
+            // Handled before the first frame is rendered, once the sample rate is known.
+            let _ = prod.push(MusicCommand::SeekTo(settings.start_position));
+            arc.position.store(settings.start_position, Ordering::SeqCst);
+            let frame = (settings.start_position * sample_rate as f64).round();
+            arc.frame.store(frame as u64, Ordering::SeqCst);
+        }
+        let amplifier = GainRamp::new(settings.amplifier);
+        let rate_gain = if settings.compensate_rate_gain {
+            rate_compensation(settings.playback_rate)
+        } else {
+            1.
+        };
         let renderer = MusicRenderer {
             clip,
             settings,
             state: Arc::downgrade(&arc),
             cons,
             paused: true,
+            finished: false,
+            loops_done: 0,
             index: 0,
             last_sample_rate: 1,
             low_pass: 0.,
@@ -246,64 +651,336 @@ impl Music {
 
             fade_time: 0,
             fade_current: 0,
+
+            mute: GainRamp::new(1.),
+            amplifier,
+
+            previous_clip: None,
+            clip_fade: GainRamp::new(1.),
+
+            stop_at: None,
+            balance: (1., 1.),
+            rate_gain,
+            loop_region: None,
+            loop_armed: false,
+            loop_beats: None,
+            mixer_state,
+            removing: false,
         };
-        (Self { arc, prod }, renderer)
+        (
+            Self {
+                tag: MusicTag::new(),
+                id,
+                arc,
+                control: Arc::new(Mutex::new(Control {
+                    prod,
+                    length,
+                    stopped: false,
+                })),
+                sample_rate,
+            },
+            renderer,
+        )
     }
 
-    pub fn play(&mut self) -> Result<()> {
-        self.prod
-            .push(MusicCommand::Resume)
+    #[inline(always)]
+    pub fn id(&self) -> RendererId {
+        self.id
+    }
+
+    fn send(&self, cmd: MusicCommand, context: &'static str) -> Result<()> {
+        let mut control = self.control.lock().unwrap();
+        if control.stopped || self.arc.removed.load(Ordering::Acquire) {
+            return Ok(());
+        }
+        control
+            .prod
+            .push(cmd)
             .map_err(buffer_is_full)
-            .context("play music")
+            .context(context)
+    }
+
+    pub fn play(&mut self) -> Result<()> {
+        self.send(MusicCommand::Resume, "play music")
+    }
+
+    /// Plays again from the beginning, reusing the resident renderer.
+    ///
+    /// With [`MusicParams::keep_alive`] the renderer stays in the mixer after
+    /// the music finishes until this handle is dropped, so replaying never
+    /// needs a new `Music`.
+    pub fn restart(&mut self) -> Result<()> {
+        self.send(MusicCommand::Restart, "restart")
+    }
+
+    /// Fades out and removes the renderer from the mixer for good, unlike
+    /// [`Music::pause`]. Afterwards this handle and its clones are inert:
+    /// further commands are accepted and ignored.
+    pub fn stop(&mut self) -> Result<()> {
+        self.send(MusicCommand::Remove, "stop music")?;
+        self.control.lock().unwrap().stopped = true;
+        Ok(())
     }
 
     pub fn pause(&mut self) -> Result<()> {
-        self.prod
-            .push(MusicCommand::Pause)
-            .map_err(buffer_is_full)
-            .context("pause")
+        self.send(MusicCommand::Pause, "pause")
     }
 
     pub fn paused(&mut self) -> bool {
         self.arc.paused.load(Ordering::SeqCst)
     }
 
+    /// Ramps to `amp` over a few milliseconds so slider drags don't zipper.
     pub fn set_amplifier(&mut self, amp: f32) -> Result<()> {
-        self.prod
-            .push(MusicCommand::SetAmplifier(amp))
-            .map_err(buffer_is_full)
-            .context("set amplifier")
+        self.send(MusicCommand::SetAmplifier(amp), "set amplifier")
+    }
+
+    /// Jumps to `amp` on the next frame, e.g. for an instant mute.
+    pub fn set_amplifier_immediate(&mut self, amp: f32) -> Result<()> {
+        self.send(MusicCommand::SetAmplifierImmediate(amp), "set amplifier")
     }
 
+    /// Moves the playhead to `position` seconds, clamped to `[0, length]`.
+    ///
+    /// Seeking to the end of a non-looping clip pauses it and marks it
+    /// finished. Out-of-range positions are never an error; this only fails
+    /// if the command buffer is full.
     pub fn seek_to(&mut self, position: f64) -> Result<()> {
-        self.prod
-            .push(MusicCommand::SeekTo(position))
-            .map_err(buffer_is_full)
-            .context("seek to")
+        self.send(MusicCommand::SeekTo(position), "seek to")
+    }
+
+    /// Moves the playhead to a frame of the clip, clamped to its length. The
+    /// renderer sets its frame index directly, so the playhead lands exactly
+    /// on `frame` as long as the clip plays at the output sample rate and a
+    /// playback rate of 1.
+    pub fn seek_to_frame(&mut self, frame: u64) -> Result<()> {
+        self.send(MusicCommand::SeekToFrame(frame), "seek to frame")
+    }
+
+    /// Sets A/B loop bounds in seconds, or clears them with `None`. The loop
+    /// takes effect the next time the playhead crosses `end`; if it is
+    /// already past `end`, playback continues until it is moved back.
+    pub fn set_loop_region(&mut self, region: Option<(f64, f64)>) -> Result<()> {
+        self.send(MusicCommand::SetLoopRegion(region), "set loop region")
+    }
+
+    /// Like [`Music::set_loop_region`] with bounds in beats of the master
+    /// tempo, see [`AudioManager::set_bpm`](crate::AudioManager::set_bpm).
+    /// The bounds follow tempo changes while playing.
+    pub fn set_loop_region_beats(&mut self, beats: Option<(f64, f64)>) -> Result<()> {
+        self.send(MusicCommand::SetLoopRegionBeats(beats), "set loop region")
+    }
+
+    /// Scales the left and right output channels independently.
+    pub fn set_balance(&mut self, left: f32, right: f32) -> Result<()> {
+        self.send(MusicCommand::SetBalance(left, right), "set balance")
+    }
+
+    /// Plays from `start` until `end` seconds once, then finishes.
+    pub fn play_region(&mut self, start: f64, end: f64) -> Result<()> {
+        self.send(MusicCommand::PlayRegion(start, end), "play region")
+    }
+
+    /// Seeks to `fraction` of the clip's length, clamped to `[0, 1]`.
+    pub fn seek_fraction(&mut self, fraction: f64) -> Result<()> {
+        let length = self.control.lock().unwrap().length;
+        self.seek_to(fraction.clamp(0., 1.) * length)
     }
 
     pub fn set_low_pass(&mut self, low_pass: f32) -> Result<()> {
-        self.prod
-            .push(MusicCommand::SetLowPass(low_pass))
-            .map_err(buffer_is_full)
-            .context("set low pass")
+        self.send(MusicCommand::SetLowPass(low_pass), "set low pass")
     }
 
     pub fn fade_in(&mut self, time: f64) -> Result<()> {
-        self.prod
-            .push(MusicCommand::FadeIn(time))
-            .map_err(buffer_is_full)
-            .context("fade in")
+        self.send(MusicCommand::FadeIn(time), "fade in")
     }
 
     pub fn fade_out(&mut self, time: f64) -> Result<()> {
-        self.prod
-            .push(MusicCommand::FadeOut(time))
+        self.send(MusicCommand::FadeOut(time), "fade out")
+    }
+
+    /// Swaps the clip being played, continuing from the current position with
+    /// a short crossfade. The new clip must have the same sample rate.
+    pub fn set_clip(&mut self, clip: AudioClip) -> Result<()> {
+        if clip.sample_rate() != self.sample_rate {
+            bail!(
+                "clip sample rate {} does not match {}",
+                clip.sample_rate(),
+                self.sample_rate
+            );
+        }
+        let length = clip.length();
+        let mut control = self.control.lock().unwrap();
+        if control.stopped || self.arc.removed.load(Ordering::Acquire) {
+            return Ok(());
+        }
+        control
+            .prod
+            .push(MusicCommand::SetClip(clip))
             .map_err(buffer_is_full)
-            .context("fade out")
+            .context("set clip")?;
+        control.length = length;
+        Ok(())
     }
 
+    /// Gates the output to silence while keeping the amplifier, so unmuting restores it exactly.
+    pub fn set_muted(&mut self, muted: bool) -> Result<()> {
+        self.send(MusicCommand::SetMuted(muted), "set muted")
+    }
+
+    /// Playhead in seconds, advanced continuously between audio blocks using
+    /// the wall clock so progress bars don't tick in buffer-sized steps.
     pub fn position(&self) -> f64 {
+        let (state, published_at) = self.snapshot();
+        if state.paused || state.finished {
+            return state.position;
+        }
+        let elapsed = (self.arc.epoch.elapsed().as_secs_f64() - published_at)
+            .clamp(0., MAX_POSITION_EXTRAPOLATION);
+        state.position + elapsed * state.playback_rate
+    }
+
+    /// Playhead as of the last rendered block, without interpolation.
+    pub fn raw_position(&self) -> f64 {
         self.arc.position.load(Ordering::SeqCst)
     }
+
+    /// Playhead in frames of the clip as of the last rendered block, read
+    /// from the renderer's frame index rather than rounded through seconds.
+    pub fn position_frames(&self) -> u64 {
+        self.arc.frame.load(Ordering::Relaxed)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.state().finished
+    }
+
+    /// RMS level of this music's own output over the last rendered block.
+    ///
+    /// Metering starts on the first call, so the first reading is `0`.
+    pub fn current_level(&self) -> f32 {
+        if !self.arc.metering.swap(true, Ordering::Relaxed) {
+            return 0.;
+        }
+        self.arc.level.load(Ordering::Relaxed)
+    }
+
+    /// Resolves once the playhead reaches the end of a non-looping clip.
+    #[cfg(feature = "async")]
+    pub fn finished(&self) -> Finished<'_, impl Fn() -> bool + Unpin + '_> {
+        Finished::new(&self.arc.finish_waker, move || self.is_finished())
+    }
+
+    /// Reads all published fields from the same renderer block.
+    pub fn state(&self) -> MusicState {
+        self.snapshot().0
+    }
+
+    /// [`Music::state`] along with the time it was published.
+    fn snapshot(&self) -> (MusicState, f64) {
+        let state = &self.arc;
+        loop {
+            let seq = state.seq.load(Ordering::Acquire);
+            if seq & 1 == 1 {
+                hint::spin_loop();
+                continue;
+            }
+            let snapshot = MusicState {
+                position: state.position.load(Ordering::Relaxed),
+                amplifier: state.amplifier.load(Ordering::Relaxed),
+                playback_rate: state.playback_rate.load(Ordering::Relaxed),
+                paused: state.paused.load(Ordering::Relaxed),
+                finished: state.finished.load(Ordering::Relaxed),
+            };
+            let published_at = state.published_at.load(Ordering::Relaxed);
+            atomic::fence(Ordering::Acquire);
+            if state.seq.load(Ordering::Relaxed) == seq {
+                return (snapshot, published_at);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{backend::manual::ManualBackend, AudioManager};
+
+    const TOLERANCE: f32 = 1e-4;
+
+    /// 0.1 s at 48 kHz whose value at each frame is its position in the clip,
+    /// from `0` up to just below `1`.
+    fn ramp() -> AudioClip {
+        let frames = (0..4800).map(|i| Frame(i as f32 / 4800., i as f32 / 4800.));
+        AudioClip::from_raw(frames.collect(), 48000)
+    }
+
+    fn setup(clip: AudioClip, params: MusicParams) -> (ManualBackend, AudioManager, Music) {
+        let backend = ManualBackend::new(48000, 2);
+        let mut manager = AudioManager::new(backend.clone()).unwrap();
+        let music = manager.create_music(clip, params).unwrap();
+        (backend, manager, music)
+    }
+
+    #[test]
+    fn seek_moves_the_playhead() {
+        let (backend, _manager, mut music) = setup(ramp(), MusicParams::default());
+        music.seek_to(0.05).unwrap();
+        music.play().unwrap();
+        let frames = backend.render(2);
+        assert!((frames[0].0 - 0.5).abs() < TOLERANCE);
+        assert!((frames[1].0 - 2401. / 4800.).abs() < TOLERANCE);
+    }
+
+    #[test]
+    fn seeks_and_reports_exact_frames() {
+        let (backend, _manager, mut music) = setup(ramp(), MusicParams::default());
+        music.seek_to_frame(1234).unwrap();
+        music.play().unwrap();
+        let frames = backend.render(100);
+        assert!((frames[0].0 - 1234. / 4800.).abs() < TOLERANCE);
+        assert_eq!(music.position_frames(), 1334);
+    }
+
+    #[test]
+    fn loops_back_to_the_start() {
+        let params = MusicParams {
+            loop_mix_time: 0.,
+            ..Default::default()
+        };
+        let (backend, _manager, mut music) = setup(ramp(), params);
+        music.play().unwrap();
+        backend.render(4800);
+        for (i, frame) in backend.render(10).iter().enumerate() {
+            assert!((frame.0 - i as f32 / 4800.).abs() < TOLERANCE);
+        }
+        assert!(!music.is_finished());
+    }
+
+    #[test]
+    fn finishes_without_looping() {
+        let (backend, _manager, mut music) = setup(ramp(), MusicParams::default());
+        music.play().unwrap();
+        let frames = backend.render(4810);
+        assert!((frames[4799].0 - 4799. / 4800.).abs() < TOLERANCE);
+        assert_eq!(frames[4809].0, 0.);
+        assert!(music.is_finished());
+    }
+
+    #[test]
+    fn fades_in_and_out_linearly() {
+        let clip = AudioClip::from_raw(vec![Frame(1., 1.); 48000], 48000);
+        let (backend, _manager, mut music) = setup(clip, MusicParams::default());
+        music.fade_in(0.01).unwrap();
+        let frames = backend.render(960);
+        assert!((frames[0].0 - 1. / 480.).abs() < TOLERANCE);
+        assert!((frames[239].0 - 0.5).abs() < TOLERANCE);
+        assert!(frames[480..].iter().all(|frame| frame.0 == 1.));
+
+        music.fade_out(0.01).unwrap();
+        let frames = backend.render(960);
+        assert!((frames[239].0 - 0.5).abs() < TOLERANCE);
+        assert!(frames[480..].iter().all(|frame| frame.0 == 0.));
+        assert!(music.paused());
+    }
 }
@@ -0,0 +1,163 @@
+use crate::{buffer_is_full, ramp::GainRamp, Frame, Renderer, RendererId};
+use anyhow::{Context, Result};
+use ringbuf::{HeapConsumer, HeapProducer, HeapRb};
+use std::sync::{
+    atomic::{AtomicU8, Ordering},
+    Arc, Weak,
+};
+
+/// What a [`PushSource`] outputs while its buffer is empty.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UnderrunFill {
+    /// Cut to silence immediately.
+    #[default]
+    Silence = 0,
+    /// Repeat the last frame until data arrives.
+    HoldLast = 1,
+    /// Ramp the last frame down over a few milliseconds, then stay silent.
+    /// Playback ramps back up when data arrives.
+    FadeToSilence = 2,
+}
+
+impl UnderrunFill {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            1 => Self::HoldLast,
+            2 => Self::FadeToSilence,
+            _ => Self::Silence,
+        }
+    }
+}
+
+pub(crate) struct PushSourceRenderer {
+    underrun_fill: Weak<AtomicU8>,
+    cons: HeapConsumer<Frame>,
+    last: Frame,
+    /// Gain of [`UnderrunFill::FadeToSilence`], lowered while starved.
+    gain: GainRamp,
+}
+
+impl PushSourceRenderer {
+    /// Next frame to output, or `None` once starved under [`UnderrunFill::Silence`].
+    #[inline]
+    fn next(&mut self, fill: UnderrunFill, sample_rate: u32) -> Option<Frame> {
+        match (self.cons.pop(), fill) {
+            (Some(frame), UnderrunFill::FadeToSilence) => {
+                self.last = frame;
+                if self.gain.target() != 1. {
+                    self.gain.declick_to(1., sample_rate);
+                }
+                Some(frame * self.gain.next())
+            }
+            (Some(frame), _) => {
+                self.last = frame;
+                Some(frame)
+            }
+            (None, UnderrunFill::Silence) => None,
+            (None, UnderrunFill::HoldLast) => Some(self.last),
+            (None, UnderrunFill::FadeToSilence) => {
+                if self.gain.target() != 0. {
+                    self.gain.declick_to(0., sample_rate);
+                }
+                Some(self.last * self.gain.next())
+            }
+        }
+    }
+
+    fn fill(&self) -> UnderrunFill {
+        self.underrun_fill
+            .upgrade()
+            .map_or(UnderrunFill::Silence, |fill| {
+                UnderrunFill::from_u8(fill.load(Ordering::Relaxed))
+            })
+    }
+}
+
+impl Renderer for PushSourceRenderer {
+    fn alive(&self) -> bool {
+        self.underrun_fill.strong_count() != 0
+    }
+
+    fn active_voices(&self) -> usize {
+        !self.cons.is_empty() as usize
+    }
+
+    fn render_mono(&mut self, sample_rate: u32, data: &mut [f32]) {
+        let fill = self.fill();
+        for sample in data.iter_mut() {
+            let Some(frame) = self.next(fill, sample_rate) else {
+                break;
+            };
+            *sample += frame.avg();
+        }
+    }
+
+    fn render_stereo(&mut self, sample_rate: u32, data: &mut [f32]) {
+        let fill = self.fill();
+        for sample in data.chunks_exact_mut(2) {
+            let Some(frame) = self.next(fill, sample_rate) else {
+                break;
+            };
+            sample[0] += frame.0;
+            sample[1] += frame.1;
+        }
+    }
+}
+
+/// Streams frames generated by the host (network audio, TTS, ...) into the mixer.
+///
+/// Frames are played at the output sample rate without resampling. When the
+/// buffer runs dry the source fills the gap as set by
+/// [`PushSource::set_underrun_fill`] until more frames arrive.
+pub struct PushSource {
+    id: RendererId,
+    underrun_fill: Arc<AtomicU8>,
+    prod: HeapProducer<Frame>,
+}
+
+impl PushSource {
+    pub(crate) fn new(id: RendererId, capacity: usize) -> (PushSource, PushSourceRenderer) {
+        let (prod, cons) = HeapRb::new(capacity).split();
+        let underrun_fill = Arc::new(AtomicU8::new(UnderrunFill::default() as u8));
+        let renderer = PushSourceRenderer {
+            underrun_fill: Arc::downgrade(&underrun_fill),
+            cons,
+            last: Frame::default(),
+            gain: GainRamp::new(1.),
+        };
+        (
+            Self {
+                id,
+                underrun_fill,
+                prod,
+            },
+            renderer,
+        )
+    }
+
+    #[inline(always)]
+    pub fn id(&self) -> RendererId {
+        self.id
+    }
+
+    pub fn set_underrun_fill(&mut self, fill: UnderrunFill) {
+        self.underrun_fill.store(fill as u8, Ordering::Relaxed);
+    }
+
+    /// Queues as many of `frames` as fit, returning how many were taken.
+    ///
+    /// Fails only if none could be queued because the buffer is full.
+    pub fn push(&mut self, frames: &[Frame]) -> Result<usize> {
+        let written = self.prod.push_slice(frames);
+        if written == 0 && !frames.is_empty() {
+            return Err(buffer_is_full(())).context("push frames");
+        }
+        Ok(written)
+    }
+
+    /// Number of frames that currently fit in the buffer.
+    pub fn free_len(&self) -> usize {
+        self.prod.free_len()
+    }
+}
@@ -1,89 +1,679 @@
-use crate::{buffer_is_full, AudioClip, Renderer};
-use anyhow::{Context, Result};
+#[cfg(feature = "async")]
+use super::finish::{FinishWaker, Finished};
+use crate::{
+    buffer_is_full,
+    tag::{SfxTag, Tagged},
+    AudioClip, Frame, Renderer, RendererId, ResampleQuality,
+};
+use anyhow::{bail, Context, Result};
 use ringbuf::{HeapConsumer, HeapProducer, HeapRb};
-use std::sync::{Arc, Weak};
+use std::{
+    collections::hash_map::RandomState,
+    hash::BuildHasher,
+    ptr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex, Weak,
+    },
+    time::{Duration, Instant},
+};
+
+/// Time over which looping voices fade out once every handle is dropped.
+const ORPHAN_FADE_TIME: f64 = 0.05;
 
 #[derive(Debug, Clone)]
 pub struct PlaySfxParams {
     pub amplifier: f32,
+    /// Restart from the beginning when the clip ends, until faded out.
+    pub looping: bool,
+    /// Seconds over which a looping voice blends its end into its start.
+    /// `None` or `0` loops without a crossfade.
+    pub loop_crossfade: Option<f64>,
+    /// Seconds to ramp the voice in from silence; `0` starts at full gain.
+    pub fade_in: f64,
+    pub playback_rate: f64,
+    /// Seconds into the clip the voice starts reading from, clamped to its length.
+    pub start_offset: f64,
+    /// Picks `amplifier` uniformly from this range on every play.
+    pub amplifier_range: Option<(f32, f32)>,
+    /// Picks `playback_rate` uniformly from this range on every play.
+    pub rate_range: Option<(f64, f64)>,
+    /// Higher priorities are stolen last, both when this Sfx runs out of
+    /// voices and when the mixer's polyphony cap is hit.
+    pub priority: u8,
+    pub resample_quality: ResampleQuality,
 }
 impl Default for PlaySfxParams {
     fn default() -> Self {
-        Self { amplifier: 1. }
+        Self {
+            amplifier: 1.,
+            looping: false,
+            loop_crossfade: None,
+            fade_in: 0.,
+            playback_rate: 1.,
+            start_offset: 0.,
+            amplifier_range: None,
+            rate_range: None,
+            priority: 0,
+            resample_quality: ResampleQuality::default(),
+        }
+    }
+}
+
+/// Creation parameters of an [`Sfx`], see
+/// [`AudioManager::create_sfx_with_params`](crate::AudioManager::create_sfx_with_params).
+#[derive(Debug, Clone, Default)]
+pub struct SfxParams {
+    /// Bounds both the queued commands and the voices sounding at once; 64
+    /// when `None`.
+    pub buffer_size: Option<usize>,
+    /// See [`AudioManager::set_bypass_master`](crate::AudioManager::set_bypass_master).
+    pub bypass_master: bool,
+}
+
+/// Identifies a voice started by [`Sfx::play`].
+///
+/// Ids count up from `0` per `Sfx`, shared by its clones, and are never
+/// reused, so they can be stored to identify a voice without ABA confusion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VoiceId(pub(crate) u64);
+
+impl VoiceId {
+    #[inline(always)]
+    pub fn get(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Outcome of [`Sfx::play`].
+///
+/// This only covers the Sfx's own voices. The mixer's cap from
+/// [`AudioManager::set_max_active`](crate::AudioManager::set_max_active)
+/// stops whole renderers later on the audio thread, which is not reported
+/// here.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlayResult {
+    /// A voice was queued in a free slot and starts with the next rendered block.
+    Started(VoiceId),
+    /// Like `Started`, but another voice was cut to make room for it.
+    Stolen(VoiceId),
+    /// Ignored because of [`Sfx::set_trigger_limit`], because every voice is
+    /// taken by one of higher priority, because the command buffer is full,
+    /// or because the renderer left the mixer, see [`Sfx::set_keep_alive`].
+    Dropped,
+}
+
+impl PlayResult {
+    pub fn voice(&self) -> Option<VoiceId> {
+        match self {
+            Self::Started(voice) | Self::Stolen(voice) => Some(*voice),
+            Self::Dropped => None,
+        }
+    }
+}
+
+struct SharedState {
+    /// Number of voices that have stopped, compared against the plays sent.
+    completed: AtomicU64,
+    /// Id + 1 of the voice holding each slot, `0` when free. Plays claim a
+    /// slot on the handle, the renderer frees it when the voice ends.
+    slots: Box<[AtomicU64]>,
+    /// See [`Sfx::set_keep_alive`].
+    keep_alive: AtomicBool,
+    /// Every voice ended without `keep_alive`, so the renderer left the mixer.
+    removed: AtomicBool,
+
+    #[cfg(feature = "async")]
+    finish_waker: FinishWaker,
+}
+
+/// Voice id and the slot it was given.
+#[derive(Clone, Copy)]
+struct Claim {
+    id: u64,
+    slot: usize,
+}
+
+enum SfxCommand {
+    Play(PlaySfxParams, Claim),
+    /// Plays a pre-pitched variant instead of the Sfx's own clip.
+    PlayVariant(PlaySfxParams, AudioClip, Claim),
+    FadeOut(f64),
+}
+
+struct Voice {
+    claim: Claim,
+    /// Overrides the renderer's clip, see [`Sfx::precompute_pitches`].
+    clip: Option<AudioClip>,
+    position: f64,
+    params: PlaySfxParams,
+    gain: f32,
+    /// Gain change per second. Voices fading out stop once silent.
+    fade_rate: f32,
+}
+
+impl Voice {
+    fn new(params: PlaySfxParams, claim: Claim, clip: Option<AudioClip>, length: f64) -> Self {
+        let (gain, fade_rate) = if params.fade_in > 0. {
+            (0., 1. / params.fade_in as f32)
+        } else {
+            (1., 0.)
+        };
+        Self {
+            claim,
+            clip,
+            position: params.start_offset.clamp(0., length),
+            params,
+            gain,
+            fade_rate,
+        }
+    }
+
+    /// Ramps the voice to silence over `time` seconds, stopping it there.
+    fn fade_out(&mut self, time: f64) {
+        self.fade_rate = if time > 0. && self.gain > 0. {
+            -self.gain / time as f32
+        } else {
+            f32::NEG_INFINITY
+        };
+    }
+
+    #[inline]
+    fn next(&mut self, clip: &AudioClip, delta: f64) -> Option<Frame> {
+        let clip = self.clip.as_ref().unwrap_or(clip);
+        let length = clip.length();
+        let crossfade = self
+            .params
+            .loop_crossfade
+            .filter(|&time| self.params.looping && time > 0.)
+            .map(|time| time.min(length));
+        if let Some(time) = crossfade {
+            // The start was already playing for `time` seconds under the end.
+            if self.position >= length {
+                self.position = self.position - length + time;
+            }
+        }
+        let quality = self.params.resample_quality;
+        let mut frame = match clip.sample_with(self.position, quality) {
+            Some(frame) => frame,
+            None if self.params.looping && clip.frame_count() != 0 => {
+                self.position %= clip.length();
+                clip.sample_with(self.position, quality)?
+            }
+            None => return None,
+        };
+        if let Some(time) = crossfade {
+            let into = self.position - (length - time);
+            if into >= 0. {
+                let mix = (into / time) as f32;
+                frame =
+                    frame * (1. - mix) + clip.sample_with(into, quality).unwrap_or_default() * mix;
+            }
+        }
+        self.position += delta * self.params.playback_rate;
+        if self.fade_rate != 0. {
+            self.gain = (self.gain + self.fade_rate * delta as f32).clamp(0., 1.);
+            if self.fade_rate < 0. {
+                if self.gain == 0. {
+                    return None;
+                }
+            } else if self.gain == 1. {
+                self.fade_rate = 0.;
+            }
+        }
+        Some(frame * (self.params.amplifier * self.gain))
     }
 }
 
 pub(crate) struct SfxRenderer {
     clip: AudioClip,
-    arc: Weak<()>,
-    cons: HeapConsumer<(f64, PlaySfxParams)>,
+    state: Weak<SharedState>,
+    cons: HeapConsumer<SfxCommand>,
+    /// Holds a voice per slot at most, so it never grows past its capacity.
+    voices: Vec<Voice>,
+    /// Every handle is gone, so looping voices have been told to fade out.
+    orphaned: bool,
+    /// A voice was started at some point, so running out of them finishes.
+    played: bool,
+}
+
+impl SfxRenderer {
+    fn prepare(&mut self) {
+        while let Some(cmd) = self.cons.pop() {
+            match cmd {
+                SfxCommand::Play(params, claim) => {
+                    let length = self.clip.length();
+                    self.start_voice(Voice::new(params, claim, None, length))
+                }
+                SfxCommand::PlayVariant(params, clip, claim) => {
+                    let length = clip.length();
+                    self.start_voice(Voice::new(params, claim, Some(clip), length))
+                }
+                SfxCommand::FadeOut(time) => {
+                    for voice in &mut self.voices {
+                        voice.fade_out(time);
+                    }
+                }
+            }
+        }
+        if !self.orphaned && self.state.strong_count() == 0 {
+            // Nothing could stop a looping voice anymore.
+            self.orphaned = true;
+            for voice in self.voices.iter_mut().filter(|voice| voice.params.looping) {
+                voice.fade_out(ORPHAN_FADE_TIME);
+            }
+        }
+    }
+
+    fn start_voice(&mut self, voice: Voice) {
+        // The handle gave the slot away, so whatever holds it was stolen.
+        if let Some(index) = self
+            .voices
+            .iter()
+            .position(|other| other.claim.slot == voice.claim.slot)
+        {
+            self.voices[index] = voice;
+            self.publish_completed(1);
+        } else {
+            self.voices.push(voice);
+        }
+        self.played = true;
+    }
+
+    /// Whether every voice ended and the renderer should not stay resident.
+    fn finished(&self) -> bool {
+        self.played
+            && self.voices.is_empty()
+            && self.cons.is_empty()
+            && self
+                .state
+                .upgrade()
+                .is_some_and(|state| !state.keep_alive.load(Ordering::Relaxed))
+    }
+}
+
+impl SfxRenderer {
+    /// Frees the slot of a voice that ended, unless it was already stolen.
+    fn release(state: &Weak<SharedState>, voice: &Voice) {
+        if let Some(state) = state.upgrade() {
+            let _ = state.slots[voice.claim.slot].compare_exchange(
+                voice.claim.id + 1,
+                0,
+                Ordering::Release,
+                Ordering::Relaxed,
+            );
+        }
+    }
+
+    fn publish_completed(&self, stopped: usize) {
+        if stopped == 0 {
+            return;
+        }
+        if let Some(state) = self.state.upgrade() {
+            if self.finished() {
+                state.removed.store(true, Ordering::Release);
+            }
+            state
+                .completed
+                .fetch_add(stopped as u64, Ordering::Release);
+            #[cfg(feature = "async")]
+            state.finish_waker.wake();
+        }
+    }
 }
 
 impl Renderer for SfxRenderer {
     fn alive(&self) -> bool {
-        !self.cons.is_empty() || self.arc.strong_count() != 0
+        (!self.voices.is_empty() || !self.cons.is_empty() || self.state.strong_count() != 0)
+            && !self.finished()
+    }
+
+    fn active_voices(&self) -> usize {
+        self.voices.len()
+    }
+
+    fn priority(&self) -> u8 {
+        self.voices
+            .iter()
+            .map(|voice| voice.params.priority)
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn stop(&mut self) {
+        self.prepare();
+        let voices = self.voices.len();
+        for voice in self.voices.drain(..) {
+            Self::release(&self.state, &voice);
+        }
+        self.publish_completed(voices);
     }
 
     fn render_mono(&mut self, sample_rate: u32, data: &mut [f32]) {
+        self.prepare();
         let delta = 1. / sample_rate as f64;
-        let mut pop_count = 0;
-        for (position, params) in self.cons.iter_mut() {
+        let clip = &self.clip;
+        let state = &self.state;
+        let voices = self.voices.len();
+        self.voices.retain_mut(|voice| {
             for sample in data.iter_mut() {
-                if let Some(frame) = self.clip.sample(*position) {
-                    *sample += frame.avg() * params.amplifier;
+                if let Some(frame) = voice.next(clip, delta) {
+                    *sample += frame.avg();
                 } else {
-                    pop_count += 1;
-                    break;
+                    Self::release(state, voice);
+                    return false;
                 }
-                *position += delta;
             }
-        }
-        unsafe {
-            self.cons.advance(pop_count);
-        }
+            true
+        });
+        self.publish_completed(voices - self.voices.len());
     }
 
     fn render_stereo(&mut self, sample_rate: u32, data: &mut [f32]) {
+        self.prepare();
         let delta = 1. / sample_rate as f64;
-        let mut pop_count = 0;
-        for (position, params) in self.cons.iter_mut() {
+        let clip = &self.clip;
+        let state = &self.state;
+        let voices = self.voices.len();
+        self.voices.retain_mut(|voice| {
             for sample in data.chunks_exact_mut(2) {
-                if let Some(frame) = self.clip.sample(*position) {
-                    sample[0] += frame.0 * params.amplifier;
-                    sample[1] += frame.1 * params.amplifier;
+                if let Some(frame) = voice.next(clip, delta) {
+                    sample[0] += frame.0;
+                    sample[1] += frame.1;
                 } else {
-                    pop_count += 1;
-                    break;
+                    Self::release(state, voice);
+                    return false;
                 }
-                *position += delta;
             }
-        }
-        unsafe {
-            self.cons.advance(pop_count);
-        }
+            true
+        });
+        self.publish_completed(voices - self.voices.len());
     }
 }
 
+/// Command side of an [`Sfx`], shared by all of its clones so trigger
+/// limits and finish tracking cover plays from every handle.
+struct Control {
+    prod: HeapProducer<SfxCommand>,
+    /// Pre-pitched copies of the clip with the playback rate they stand for.
+    pitches: Vec<(f64, AudioClip)>,
+    played: u64,
+    /// Id handed out by the next [`Sfx::play`].
+    next_voice: u64,
+    /// Priority of the voice last given each slot.
+    priorities: Box<[u8]>,
+    /// Parameters of the last [`Sfx::play`], for [`Sfx::replay`].
+    last_params: Option<PlaySfxParams>,
+    rng: u32,
+    /// Window length and the most plays accepted within it.
+    trigger_limit: Option<(Duration, usize)>,
+    window_start: Instant,
+    window_plays: usize,
+}
+
+impl Control {
+    /// Uniform random value in `0..1`.
+    fn random(&mut self) -> f64 {
+        // xorshift32
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 17;
+        self.rng ^= self.rng << 5;
+        self.rng as f64 / (u32::MAX as f64 + 1.)
+    }
+}
+
+/// Handle triggering voices of a clip.
+///
+/// Clones control the same renderer, which stays in the mixer until every
+/// handle has been dropped.
+#[derive(Clone)]
+#[repr(C)]
 pub struct Sfx {
-    _arc: Arc<()>,
-    prod: HeapProducer<(f64, PlaySfxParams)>,
+    /// First, where the C API reads it.
+    tag: SfxTag,
+    id: RendererId,
+    clip: AudioClip,
+    arc: Arc<SharedState>,
+    control: Arc<Mutex<Control>>,
+}
+impl Tagged for Sfx {
+    const MAGIC: u32 = SfxTag::MAGIC;
+
+    unsafe fn tag_ptr(ptr: *const Self) -> *const u32 {
+        ptr::addr_of!((*ptr).tag).cast()
+    }
 }
 impl Sfx {
-    pub(crate) fn new(clip: AudioClip, buffer_size: Option<usize>) -> (Sfx, SfxRenderer) {
-        let (prod, cons) = HeapRb::new(buffer_size.unwrap_or(64)).split();
-        let arc = Arc::new(());
+    pub(crate) fn new(
+        id: RendererId,
+        clip: AudioClip,
+        buffer_size: Option<usize>,
+    ) -> (Sfx, SfxRenderer) {
+        let buffer_size = buffer_size.unwrap_or(64);
+        let (prod, cons) = HeapRb::new(buffer_size).split();
+        let arc = Arc::new(SharedState {
+            completed: AtomicU64::new(0),
+            slots: (0..buffer_size).map(|_| AtomicU64::new(0)).collect(),
+            keep_alive: AtomicBool::new(true),
+            removed: AtomicBool::new(false),
+            #[cfg(feature = "async")]
+            finish_waker: FinishWaker::default(),
+        });
         let renderer = SfxRenderer {
-            clip,
-            arc: Arc::downgrade(&arc),
+            clip: clip.clone(),
+            state: Arc::downgrade(&arc),
             cons,
+            voices: Vec::with_capacity(buffer_size),
+            orphaned: false,
+            played: false,
         };
-        (Self { _arc: arc, prod }, renderer)
+        (
+            Self {
+                tag: SfxTag::new(),
+                id,
+                clip,
+                arc,
+                control: Arc::new(Mutex::new(Control {
+                    prod,
+                    pitches: Vec::new(),
+                    played: 0,
+                    next_voice: 0,
+                    priorities: vec![0; buffer_size].into(),
+                    last_params: None,
+                    rng: RandomState::new().hash_one(id.0) as u32 | 1,
+                    trigger_limit: None,
+                    window_start: Instant::now(),
+                    window_plays: 0,
+                })),
+            },
+            renderer,
+        )
+    }
+
+    #[inline(always)]
+    pub fn id(&self) -> RendererId {
+        self.id
     }
 
-    pub fn play(&mut self, params: PlaySfxParams) -> Result<()> {
-        self.prod
-            .push((0., params))
+    /// Coalesces bursts of triggers: at most `max_plays` calls to [`Sfx::play`]
+    /// within each `window` seconds start a voice, the rest are ignored.
+    /// `None` removes the limit.
+    pub fn set_trigger_limit(&mut self, limit: Option<(f64, usize)>) {
+        let mut control = self.control.lock().unwrap();
+        control.trigger_limit =
+            limit.map(|(window, max_plays)| (Duration::from_secs_f64(window), max_plays));
+        control.window_plays = 0;
+    }
+
+    /// Renders the clip once at each of `semitones` offsets. Later plays use
+    /// the variant nearest to their playback rate and only resample the small
+    /// remainder, trading memory for audio-thread CPU. The variants follow
+    /// later [`AudioClip::set_gain`] calls on the clip.
+    pub fn precompute_pitches(&mut self, semitones: &[f32]) {
+        let sample_rate = self.clip.sample_rate();
+        let pitches = semitones
+            .iter()
+            .map(|semitones| {
+                let target = (sample_rate as f64 / 2f64.powf(*semitones as f64 / 12.)).round();
+                let resampled = self.clip.resample(target as u32, ResampleQuality::Sinc);
+                let clip = self.clip.with_shared_gain(resampled.frames().to_vec());
+                (sample_rate as f64 / target, clip)
+            })
+            .collect();
+        self.control.lock().unwrap().pitches = pitches;
+    }
+
+    /// Starts a voice, or reports that it was dropped.
+    ///
+    /// When every voice is taken, the one with the lowest priority is stolen,
+    /// the oldest among equals. If all of them have a higher priority than
+    /// `params.priority`, the play is dropped instead. Only started voices
+    /// consume a [`VoiceId`].
+    pub fn play(&mut self, mut params: PlaySfxParams) -> PlayResult {
+        let mut control = self.control.lock().unwrap();
+        let control = &mut *control;
+        control.last_params = Some(params.clone());
+        if self.arc.removed.load(Ordering::Acquire) {
+            return PlayResult::Dropped;
+        }
+        if let Some((window, max_plays)) = control.trigger_limit {
+            let now = Instant::now();
+            if now.duration_since(control.window_start) >= window {
+                control.window_start = now;
+                control.window_plays = 0;
+            }
+            if control.window_plays >= max_plays {
+                return PlayResult::Dropped;
+            }
+            control.window_plays += 1;
+        }
+        if let Some((low, high)) = params.amplifier_range.take() {
+            params.amplifier = low + (high - low) * control.random() as f32;
+        }
+        if let Some((low, high)) = params.rate_range.take() {
+            params.playback_rate = low + (high - low) * control.random();
+        }
+        let distance = |rate: f64| (params.playback_rate / rate).ln().abs();
+        let variant = control
+            .pitches
+            .iter()
+            .filter(|(rate, _)| distance(*rate) < distance(1.))
+            .min_by(|(a, _), (b, _)| distance(*a).total_cmp(&distance(*b)));
+        if control.prod.is_full() {
+            return PlayResult::Dropped;
+        }
+        let slots = &self.arc.slots;
+        let id = control.next_voice;
+        let free = slots
+            .iter()
+            .position(|slot| slot.load(Ordering::Acquire) == 0);
+        let slot = match free {
+            Some(slot) => slot,
+            None => {
+                // Ids grow with age, so the smallest is the oldest.
+                let age = |slot: usize| slots[slot].load(Ordering::Relaxed);
+                let victim = (0..slots.len())
+                    .min_by_key(|&slot| (control.priorities[slot], age(slot)))
+                    .unwrap();
+                if params.priority < control.priorities[victim] {
+                    return PlayResult::Dropped;
+                }
+                victim
+            }
+        };
+        // The victim may have ended since, leaving nothing to steal.
+        let stolen = slots[slot].swap(id + 1, Ordering::AcqRel) != 0;
+        control.priorities[slot] = params.priority;
+        let claim = Claim { id, slot };
+        let command = match variant {
+            Some((rate, clip)) => {
+                // The variant is stretched by `1 / rate`, and so are positions in it.
+                params.playback_rate /= rate;
+                params.start_offset /= rate;
+                params.loop_crossfade = params.loop_crossfade.map(|time| time / rate);
+                SfxCommand::PlayVariant(params, clip.clone(), claim)
+            }
+            None => SfxCommand::Play(params, claim),
+        };
+        // Cannot fail: the buffer had room and the lock is held.
+        let _ = control.prod.push(command);
+        let id = VoiceId(id);
+        control.played += 1;
+        control.next_voice += 1;
+        if stolen {
+            PlayResult::Stolen(id)
+        } else {
+            PlayResult::Started(id)
+        }
+    }
+
+    /// Plays again with the parameters of the last [`Sfx::play`], rolling
+    /// random ranges anew. Dropped if nothing was played yet.
+    pub fn replay(&mut self) -> PlayResult {
+        let params = self.control.lock().unwrap().last_params.clone();
+        match params {
+            Some(params) => self.play(params),
+            None => PlayResult::Dropped,
+        }
+    }
+
+    /// Keeps the renderer in the mixer once every voice has ended, so later
+    /// plays reuse it; on by default. When off, the renderer leaves the
+    /// mixer as soon as nothing is playing after the first play, and later
+    /// plays are dropped.
+    pub fn set_keep_alive(&mut self, keep_alive: bool) {
+        self.arc.keep_alive.store(keep_alive, Ordering::Relaxed);
+    }
+
+    /// Whether every voice played so far has stopped.
+    pub fn is_finished(&self) -> bool {
+        self.arc.completed.load(Ordering::Acquire) >= self.control.lock().unwrap().played
+    }
+
+    /// Resolves once every voice played so far has stopped.
+    #[cfg(feature = "async")]
+    pub fn finished(&self) -> Finished<'_, impl Fn() -> bool + Unpin + '_> {
+        let played = self.control.lock().unwrap().played;
+        Finished::new(&self.arc.finish_waker, move || {
+            self.arc.completed.load(Ordering::Acquire) >= played
+        })
+    }
+
+    /// Fades out every voice currently playing, stopping them once silent.
+    pub fn fade_out(&mut self, time: f64) -> Result<()> {
+        self.control
+            .lock()
+            .unwrap()
+            .prod
+            .push(SfxCommand::FadeOut(time))
             .map_err(buffer_is_full)
-            .context("play sfx")
+            .context("fade out")
+    }
+
+    /// Fades out this Sfx's voices over `time` seconds while fading in a
+    /// looping voice of `other` with default params.
+    pub fn crossfade_to(&mut self, other: &Sfx, time: f64) -> Result<()> {
+        self.crossfade_to_with(other, PlaySfxParams::default(), time)
+    }
+
+    /// Like [`Sfx::crossfade_to`], playing `other` with `params`.
+    ///
+    /// `params.looping` and `params.fade_in` are overridden for the incoming
+    /// voice. Fails without fading anything out if that voice is dropped.
+    pub fn crossfade_to_with(
+        &mut self,
+        other: &Sfx,
+        params: PlaySfxParams,
+        time: f64,
+    ) -> Result<()> {
+        let incoming = other.clone().play(PlaySfxParams {
+            looping: true,
+            fade_in: time,
+            ..params
+        });
+        if incoming == PlayResult::Dropped {
+            bail!("crossfade: incoming voice was dropped");
+        }
+        self.fade_out(time).context("crossfade")
     }
 }
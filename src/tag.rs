@@ -0,0 +1,63 @@
+/// Magic number carried by values handed out through the C API.
+///
+/// The FFI checks it before using a pointer and fails instead of touching an
+/// obviously bad handle: one of the wrong type, one already destroyed, or
+/// garbage. It is cleared on drop. This is a best-effort guard; it cannot
+/// make reading freed memory sound, only catch the common mistakes early.
+///
+/// Tagged types are `repr(C)` with the tag as their first field, so the tag
+/// of any handle is read from the same place whatever its real type.
+#[repr(transparent)]
+pub(crate) struct Tag<const MAGIC: u32>(u32);
+
+impl<const MAGIC: u32> Tag<MAGIC> {
+    pub(crate) const MAGIC: u32 = MAGIC;
+
+    pub(crate) const fn new() -> Self {
+        Self(MAGIC)
+    }
+}
+
+impl<const MAGIC: u32> Clone for Tag<MAGIC> {
+    fn clone(&self) -> Self {
+        Self::new()
+    }
+}
+
+impl<const MAGIC: u32> Drop for Tag<MAGIC> {
+    fn drop(&mut self) {
+        // Volatile so the store survives even though the memory is freed
+        // right after.
+        unsafe { std::ptr::write_volatile(&mut self.0, 0) }
+    }
+}
+
+pub(crate) type ManagerTag = Tag<0x5341_4d47>; // "SAMG"
+pub(crate) type ClipTag = Tag<0x5341_434c>; // "SACL"
+pub(crate) type SfxTag = Tag<0x5341_5346>; // "SASF"
+pub(crate) type MusicTag = Tag<0x5341_4d55>; // "SAMU"
+
+/// Types whose pointers the C API hands out.
+pub(crate) trait Tagged {
+    const MAGIC: u32;
+
+    /// Address of the tag, computed without forming a reference.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be non-null.
+    unsafe fn tag_ptr(ptr: *const Self) -> *const u32;
+}
+
+/// Dereferences a handle from the C API, or `None` if it is null or its tag
+/// doesn't match. The tag is read before any reference to the value exists.
+///
+/// # Safety
+///
+/// `ptr` must be null or point to at least four readable bytes.
+pub(crate) unsafe fn checked<'a, T: Tagged>(ptr: *mut T) -> Option<&'a mut T> {
+    if ptr.is_null() || T::tag_ptr(ptr).read_volatile() != T::MAGIC {
+        return None;
+    }
+    Some(&mut *ptr)
+}